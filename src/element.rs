@@ -1,39 +1,159 @@
-use std::{collections::HashMap, num::NonZeroUsize, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    time::Instant,
+};
 
 use vello::{
-    kurbo::{Affine, Circle, CubicBez, Point, Rect, RoundedRect, Size, Vec2},
+    kurbo::{
+        Affine, BezPath, Circle, CubicBez, Line, ParamCurve, Point, Rect, RoundedRect, Shape, Size,
+        Vec2,
+    },
     peniko::{Brush, Color, Fill, Stroke},
     SceneBuilder, SceneFragment,
 };
 use winit::{
-    event::{ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+    event::{
+        ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
+        VirtualKeyCode, WindowEvent,
+    },
     window::CursorIcon,
 };
 
-use crate::{ComponentHandle, RegisteredPin, Simulator};
+use crate::{
+    glyphs,
+    history::{Command, History},
+    memory::Memory,
+    project,
+    routing::{self, RouteCache, WireStyle},
+    Component, ComponentHandle, ConnectionError, RegisteredPin, SimTime, Simulator, State,
+    StateVec,
+};
+
+/// Size of newly placed elements
+const DEFAULT_SIZE: Size = Size::new(100.0, 100.0);
+/// How far the mouse can move between press and release and still count as a click, in pixels
+const CLICK_DISTANCE: f64 = 4.0;
+
+const LOW_COLOR: Color = Color::rgb8(0, 100, 0);
+const HIGH_COLOR: Color = Color::rgb8(50, 220, 50);
+const INVALID_COLOR: Color = Color::rgb8(220, 20, 60);
+const BUS_COLOR: Color = Color::rgb8(30, 144, 255);
+/// Wires nothing is driving
+const HIGHZ_COLOR: Color = Color::rgb8(160, 160, 200);
+/// Outline of pins that aren't connected to anything
+const UNCONNECTED_COLOR: Color = Color::rgb8(128, 128, 128);
+/// Lit LEDs and display segments
+const LIT_COLOR: Color = Color::rgb8(255, 90, 20);
+const UNLIT_COLOR: Color = Color::rgb8(70, 35, 25);
+/// Segments of the hexadecimal digits, bit 0 is segment a and bit 6 is segment g
+const HEX_SEGMENTS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+/// Push buttons that aren't held down
+const BUTTON_COLOR: Color = Color::rgb8(110, 110, 110);
+const SWITCH_SLOT_COLOR: Color = Color::rgb8(20, 20, 20);
+const SWITCH_KNOB_COLOR: Color = Color::rgb8(230, 230, 230);
+const SELECTION_COLOR: Color = Color::rgb8(255, 165, 0);
+const GRID_COLOR: Color = Color::rgb8(225, 225, 225);
+/// Every fifth grid line is drawn a bit darker
+const GRID_MAJOR_COLOR: Color = Color::rgb8(200, 200, 200);
+/// Grid lines closer than this on screen are thinned out, in pixels
+const MIN_GRID_SPACING: f64 = 8.0;
+/// How far right of the drivers the wires of a net with several pins join up
+const JUNCTION_OFFSET: f64 = 20.0;
+const JUNCTION_RADIUS: f64 = 4.0;
+
+pub const DEFAULT_GRID_SPACING: f64 = 20.0;
+
+/// World space grid drawn behind the elements. Elements snap to it while they're dragged, unless alt is held.
+pub struct Grid {
+    pub spacing: f64,
+    pub visible: bool,
+    pub snap: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid {
+            spacing: DEFAULT_GRID_SPACING,
+            visible: true,
+            snap: true,
+        }
+    }
+}
+
+impl Grid {
+    /// Spacings the side panel offers
+    pub const SPACINGS: RangeInclusive<f64> = 5.0..=100.0;
+
+    /// Closest grid point
+    pub fn snap(&self, point: Point) -> Point {
+        Point::new(
+            (point.x / self.spacing).round() * self.spacing,
+            (point.y / self.spacing).round() * self.spacing,
+        )
+    }
+}
 
 pub struct EventContext<'a> {
     pub set_cursor_icon: &'a dyn Fn(CursorIcon),
 }
 
 pub struct ElementManager {
-    sim: Arc<Simulator>,
+    sim: Simulator,
+    /// World space position of every pin of every element
     pin_cache: HashMap<RegisteredPin, Point>,
 
+    /// Part of the window the canvas is shown in, clicks outside of it belong to the gui
     view: Rect,
 
     zoom: f64,
     translation: Vec2,
     mouse_position: Vec2,
     last_mouse_position: Vec2,
+    /// Where the left mouse button was last pressed, to tell clicks from drags
+    press_position: Vec2,
     drag: bool,
+    modifiers: ModifiersState,
 
-    grabbed_element: Option<NonZeroUsize>,
+    selection: HashSet<ComponentHandle>,
+    /// Element the left mouse button was pressed on
+    clicked: Option<ComponentHandle>,
+    /// Push button held down with the left mouse button
+    pressed: Option<ComponentHandle>,
+    /// Whether the selection follows the mouse
+    moving: bool,
+    /// How far the mouse has dragged the selection since it was picked up
+    move_offset: Vec2,
+    /// How far the selection has actually moved after snapping, so the whole drag becomes one undo entry
+    moved: Vec2,
+    /// Point of the clicked element that gets snapped to the grid
+    grab_anchor: Point,
+    /// World space corner where a rubber band selection started
+    marquee: Option<Point>,
+    /// Output pin a new wire is being dragged from
+    wire_start: Option<RegisteredPin>,
+    /// Component picked from the palette that follows the cursor until it's placed
+    placing: Option<Component>,
+    history: History,
+    /// Whether simulated time advances on its own
+    pub running: bool,
+    /// Simulated time units per second while running
+    pub speed: f64,
+    /// When `update` last advanced the simulation
+    last_update: Option<Instant>,
+    /// Fraction of a time unit that didn't fit into the last update
+    time_remainder: f64,
+    pub grid: Grid,
+    /// Orthogonal wires from the last frame
+    routes: RouteCache,
+    pub settings: project::Settings,
     pub elements: Vec<Element>,
 }
 
 impl ElementManager {
-    pub fn new(sim: Arc<Simulator>, size: (f64, f64)) -> ElementManager {
+    pub fn new(sim: Simulator, size: (f64, f64)) -> ElementManager {
         ElementManager {
             sim,
             pin_cache: HashMap::new(),
@@ -43,28 +163,516 @@ impl ElementManager {
             translation: Vec2::ZERO,
             mouse_position: Vec2::ZERO,
             last_mouse_position: Vec2::ZERO,
+            press_position: Vec2::ZERO,
             drag: false,
+            modifiers: ModifiersState::empty(),
 
-            grabbed_element: None,
+            selection: HashSet::new(),
+            clicked: None,
+            pressed: None,
+            moving: false,
+            move_offset: Vec2::ZERO,
+            moved: Vec2::ZERO,
+            grab_anchor: Point::ZERO,
+            marquee: None,
+            wire_start: None,
+            placing: None,
+            history: History::default(),
+            running: false,
+            speed: 10.0,
+            last_update: None,
+            time_remainder: 0.0,
+            grid: Grid::default(),
+            routes: RouteCache::default(),
+            settings: project::Settings::default(),
             elements: Vec::new(),
         }
     }
 
     pub fn with_elements(mut self, elements: Vec<Element>) -> ElementManager {
         self.elements = elements;
+        self.layout();
+        self
+    }
+
+    /// Recalculates the size and pins of every element, for example after the grid spacing changed
+    pub fn layout(&mut self) {
         self.elements
             .iter_mut()
-            .for_each(|e| e.calculate_positions(&self.sim, &mut self.pin_cache));
+            .for_each(|e| e.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing));
+    }
+
+    pub fn with_settings(mut self, settings: project::Settings) -> ElementManager {
+        self.settings = settings;
+        self.set_grid_spacing(settings.grid_spacing);
         self
     }
 
+    /// Changes the grid spacing, which is saved with the project, and lays the elements out again since their
+    /// pins are spaced by it
+    pub fn set_grid_spacing(&mut self, spacing: f64) {
+        let spacing = spacing.clamp(*Grid::SPACINGS.start(), *Grid::SPACINGS.end());
+        self.grid.spacing = spacing;
+        self.settings.grid_spacing = spacing;
+        self.layout();
+    }
+
+    pub fn sim(&self) -> &Simulator {
+        &self.sim
+    }
+
     pub fn insert(&mut self, mut element: Element) {
-        element.calculate_positions(&self.sim, &mut self.pin_cache);
+        element.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing);
         self.elements.push(element);
     }
 
+    /// Advances the simulation by the real time passed since the last call, if it's running.
+    /// Returns whether it's still running so the caller knows to keep redrawing.
+    pub fn update(&mut self) -> bool {
+        if !self.running {
+            self.last_update = None;
+            return false;
+        }
+
+        let now = Instant::now();
+        if let Some(last) = self.last_update {
+            self.time_remainder += (now - last).as_secs_f64() * self.speed;
+            let duration = self.time_remainder.floor();
+            self.time_remainder -= duration;
+
+            if let Err(err) = self.sim.advance(duration as SimTime) {
+                eprintln!("WARNING: {err}");
+                self.running = false;
+            }
+        }
+
+        self.last_update = Some(now);
+        self.running
+    }
+
+    /// Advances the simulation to the next clock edge, or by a single time unit if there are no clocks
+    pub fn step(&mut self) {
+        let duration = self
+            .sim
+            .next_clock_edge()
+            .map_or(1, |edge| edge - self.sim.time());
+
+        if let Err(err) = self.sim.advance(duration) {
+            eprintln!("WARNING: {err}");
+        }
+    }
+
+    /// The ROM or RAM the memory inspector shows, which is the selected component if it's the only one
+    pub fn selected_memory(&self) -> Option<ComponentHandle> {
+        if self.selection.len() != 1 {
+            return None;
+        }
+
+        let handle = *self.selection.iter().next()?;
+        self.sim.with_memory(handle, |_| ())?;
+        Some(handle)
+    }
+
+    /// Changes the contents of a memory and lets the new words propagate right away like a toggled input, without
+    /// waiting for a step that would also clock the circuit. Like the values a running RAM stores, contents aren't
+    /// part of the undo history.
+    pub fn edit_memory<R>(
+        &mut self,
+        handle: ComponentHandle,
+        f: impl FnOnce(&mut Memory) -> R,
+    ) -> Option<R> {
+        let result = self.sim.edit_memory(handle, f);
+        self.sim.tick();
+        result
+    }
+
+    /// Sets the area of the window the canvas covers, in physical pixels like the mouse position
+    pub fn set_view(&mut self, view: Rect) {
+        self.view = view;
+    }
+
+    /// Attaches `component` to the cursor, the next click on the canvas places it
+    pub fn start_placing(&mut self, component: Component) {
+        self.placing = Some(component);
+        self.clicked = None;
+        self.moving = false;
+        self.wire_start = None;
+    }
+
+    /// Inserts the component being placed, centered on the cursor
+    fn place(&mut self) {
+        let Some(component) = self.placing.take() else {
+            return;
+        };
+
+        let handle = ComponentHandle(self.sim.insert_component(component));
+        let position = self.world_mouse_position() - DEFAULT_SIZE.to_vec2() / 2.0;
+
+        self.insert(Element::new(handle, position));
+        self.snap_element(self.elements.len() - 1);
+        self.sim.initialize();
+        self.history.record(Command::Remove { handle });
+    }
+
+    fn snapping(&self) -> bool {
+        self.grid.snap && !self.modifiers.alt()
+    }
+
+    /// Moves an element so its anchor lies on the grid
+    fn snap_element(&mut self, index: usize) {
+        if !self.snapping() {
+            return;
+        }
+
+        let element = &mut self.elements[index];
+        let anchor = element.anchor();
+
+        element.position += self.grid.snap(anchor) - anchor;
+        element.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing);
+    }
+
+    /// Carries out an operation and records it so it can be undone
+    fn execute(&mut self, command: Command) {
+        let undo = self.apply(command);
+        self.history.record(undo);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(command) = self.history.take_undo() {
+            let redo = self.apply(command);
+            self.history.push_redo(redo);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(command) = self.history.take_redo() {
+            let undo = self.apply(command);
+            self.history.push_undo(undo);
+        }
+    }
+
+    /// Applies a command to both the simulator and the elements. Returns the command that reverts it.
+    fn apply(&mut self, command: Command) -> Command {
+        // Anything in progress may refer to elements that are about to move around
+        self.clicked = None;
+        self.moving = false;
+        self.wire_start = None;
+
+        let (structural, changes_values) = (command.is_structural(), command.changes_values());
+        let inverse = match command {
+            Command::Remove { handle } => {
+                let element = self
+                    .elements
+                    .iter()
+                    .position(|element| element.component == handle)
+                    .map(|index| (index, self.elements.remove(index)));
+                let connections = self.sim.connections_of(handle);
+
+                self.pin_cache.retain(|pin, _| pin.0 != handle.0);
+                self.selection.remove(&handle);
+                let component = self.sim.remove_component(handle);
+
+                Command::Restore {
+                    handle,
+                    component,
+                    element,
+                    connections,
+                }
+            }
+            Command::Restore {
+                handle,
+                component,
+                element,
+                connections,
+            } => {
+                self.sim.restore_component(handle, component);
+                for (driver, sink) in connections {
+                    self.sim.connect(driver, sink);
+                }
+
+                if let Some((index, mut element)) = element {
+                    element.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing);
+                    self.elements
+                        .insert(index.min(self.elements.len()), element);
+                }
+
+                Command::Remove { handle }
+            }
+            Command::Move { handle, offset } => {
+                if let Some(element) = self
+                    .elements
+                    .iter_mut()
+                    .find(|element| element.component == handle)
+                {
+                    element.position += offset;
+                    element.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing);
+                }
+
+                Command::Move {
+                    handle,
+                    offset: -offset,
+                }
+            }
+            Command::Connect { driver, sink } => {
+                self.sim.connect(driver, sink);
+                Command::Disconnect { driver, sink }
+            }
+            Command::Disconnect { driver, sink } => {
+                self.sim.disconnect(driver, sink);
+                Command::Connect { driver, sink }
+            }
+            Command::SetInput { handle, value } => {
+                let previous = self
+                    .sim
+                    .replace_input(handle, value.clone())
+                    .unwrap_or(value);
+                Command::SetInput {
+                    handle,
+                    value: previous,
+                }
+            }
+            Command::FlipSwitch { handle, bit } => {
+                self.sim.flip_switch(handle, bit);
+                Command::FlipSwitch { handle, bit }
+            }
+            Command::Batch(commands) => {
+                let mut inverse = commands
+                    .into_iter()
+                    .map(|command| self.apply(command))
+                    .collect::<Vec<_>>();
+                inverse.reverse();
+
+                Command::Batch(inverse)
+            }
+        };
+
+        // Moves leave the simulation alone, so running circuits don't skip ahead
+        if structural {
+            self.sim.initialize();
+        } else if changes_values {
+            self.sim.tick();
+        }
+        inverse
+    }
+
+    fn delete_selection(&mut self) {
+        let commands = self
+            .selection
+            .iter()
+            .map(|handle| Command::Remove { handle: *handle })
+            .collect();
+        self.execute(Command::Batch(commands));
+    }
+
+    /// Clipboard text for the selected elements, with the components they show and the wires between them.
+    /// It's a regular project file with positions relative to the top left corner of the selection.
+    pub fn copy_selection(&self) -> Option<String> {
+        let selected = self
+            .elements
+            .iter()
+            .filter(|element| self.selection.contains(&element.component))
+            .collect::<Vec<_>>();
+
+        let origin = selected
+            .iter()
+            .map(|element| element.bounds())
+            .reduce(|a, b| a.union(b))?
+            .origin();
+
+        let handles = selected
+            .iter()
+            .map(|element| element.component)
+            .collect::<Vec<_>>();
+        let (sim, ids) = self.sim.extract(&handles);
+
+        let elements = selected
+            .iter()
+            .map(|element| {
+                Element::new(
+                    ComponentHandle(ids[&element.component.0]),
+                    (element.position - origin).to_point(),
+                )
+                .with_size(element.size)
+            })
+            .collect::<Vec<_>>();
+
+        match project::to_string(&sim, &elements, &project::Settings::default()) {
+            Ok(text) => Some(text),
+            Err(err) => {
+                eprintln!("WARNING: can't copy selection: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn cut_selection(&mut self) -> Option<String> {
+        let text = self.copy_selection()?;
+        self.delete_selection();
+
+        Some(text)
+    }
+
+    /// Inserts a copied circuit with fresh handles, its top left corner at the cursor. The pasted elements
+    /// become the new selection.
+    pub fn paste(&mut self, text: &str) {
+        let (sim, elements, _) = match project::from_str(text) {
+            Ok(circuit) => circuit,
+            Err(err) => {
+                eprintln!("WARNING: can't paste: {err}");
+                return;
+            }
+        };
+
+        let ids = self.sim.merge(&sim);
+        let mut origin = self.world_mouse_position();
+        if self.snapping() {
+            origin = self.grid.snap(origin);
+        }
+        let origin = origin.to_vec2();
+
+        self.selection.clear();
+        for element in elements {
+            let handle = ComponentHandle(ids[&element.component.0]);
+
+            self.insert(Element::new(handle, element.position + origin).with_size(element.size));
+            self.selection.insert(handle);
+        }
+
+        self.sim.initialize();
+        self.history.record(Command::Batch(
+            ids.values()
+                .map(|index| Command::Remove {
+                    handle: ComponentHandle(*index),
+                })
+                .collect(),
+        ));
+    }
+
+    /// Moves every selected element
+    fn move_selection(&mut self, offset: Vec2) {
+        for element in &mut self.elements {
+            if self.selection.contains(&element.component) {
+                element.position += offset;
+                element.calculate_positions(&self.sim, &mut self.pin_cache, self.grid.spacing);
+            }
+        }
+    }
+
+    /// Index of the element under the cursor
+    fn hovered_element(&self) -> Option<usize> {
+        let pos = self.world_mouse_position();
+
+        self.elements
+            .iter()
+            .rposition(|element| !matches!(element.hittest(&self.pin_cache, pos), HitResult::NoHit))
+    }
+
+    /// Wires attached to the pin under the cursor, as `(driver, sink)`. An output can drive several sinks.
+    fn hovered_wires(&self) -> Vec<(RegisteredPin, RegisteredPin)> {
+        let pos = self.world_mouse_position();
+
+        self.elements
+            .iter()
+            .rev()
+            .find_map(|element| match element.hittest(&self.pin_cache, pos) {
+                HitResult::HitInput(pin) | HitResult::HitOutput(pin) => {
+                    self.sim.net_of(pin).map(|net| {
+                        net.wires()
+                            .iter()
+                            .filter(|(driver, sink)| *driver == pin || *sink == pin)
+                            .copied()
+                            .collect()
+                    })
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Bit of the switch under the mouse, if `handle` is a toggle switch or DIP switch. Toggle switches flip
+    /// wherever they're clicked.
+    fn switch_at(&self, handle: ComponentHandle) -> Option<usize> {
+        let element = self
+            .elements
+            .iter()
+            .find(|element| element.component == handle)?;
+
+        let switches = match &*self.sim.get_component(&handle).borrow() {
+            Component::ToggleSwitch(_) => return Some(0),
+            Component::DipSwitch(value) => value.width(),
+            _ => return None,
+        };
+
+        let pos = self.world_mouse_position();
+        element
+            .switch_bounds(switches)
+            .iter()
+            .position(|bounds| bounds.contains(pos))
+    }
+
+    fn mouse_in_view(&self) -> bool {
+        self.view.contains(self.mouse_position.to_point())
+    }
+
+    /// Mouse position in world space
+    fn world_mouse_position(&self) -> Point {
+        (self.mouse_position / self.zoom - self.translation / self.zoom).to_point()
+    }
+
+    /// Holds a push button down or lets go of it, and lets the change propagate right away like a toggled input.
+    /// Returns false if the component isn't a push button.
+    fn press_button(&mut self, handle: ComponentHandle, pressed: bool) -> bool {
+        let is_button = self.sim.press_button(handle, pressed);
+        if is_button {
+            self.sim.tick();
+        }
+        is_button
+    }
+
+    /// Pin under the cursor and whether the wire being dragged from `start` could be connected to it.
+    /// `None` if the cursor isn't over a pin.
+    fn wire_target(
+        &self,
+        start: RegisteredPin,
+    ) -> Option<(RegisteredPin, Result<(), ConnectionError>)> {
+        let pos = self.world_mouse_position();
+
+        self.elements
+            .iter()
+            .find_map(|element| match element.hittest(&self.pin_cache, pos) {
+                HitResult::HitInput(pin) | HitResult::HitOutput(pin) => {
+                    Some((pin, self.sim.check_connection(start, pin)))
+                }
+                _ => None,
+            })
+    }
+
     pub fn event(&mut self, ctx: &EventContext, window_event: &WindowEvent) {
+        // Any mouse button going up or the window losing focus lets go of a held push button, so a release the
+        // canvas never sees can't leave it stuck
+        if let WindowEvent::MouseInput {
+            state: ElementState::Released,
+            ..
+        }
+        | WindowEvent::Focused(false) = window_event
+        {
+            if let Some(handle) = self.pressed.take() {
+                self.press_button(handle, false);
+            }
+        }
+
         match window_event {
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            }
+            | WindowEvent::MouseWheel { .. }
+                if !self.mouse_in_view() => {}
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => self.placing = None,
             WindowEvent::MouseInput {
                 state,
                 button: MouseButton::Middle,
@@ -82,17 +690,60 @@ impl ElementManager {
                 button: MouseButton::Left,
                 ..
             } => {
-                let pos = self.mouse_position / self.zoom - self.translation / self.zoom;
+                if self.placing.is_some() {
+                    self.place();
+                    return;
+                }
 
-                for (i, element) in self.elements.iter().enumerate() {
-                    let result = element.hittest(&self.sim, &self.pin_cache, pos.to_point());
+                self.press_position = self.mouse_position;
+                let pos = self.world_mouse_position();
 
-                    match result {
-                        HitResult::Hit => {
-                            debug_assert!(self.grabbed_element.is_none());
-                            self.grabbed_element = Some((i + 1).try_into().unwrap());
+                // The topmost element wins, which is the one drawn last
+                let hit = self.elements.iter().rev().find_map(|element| {
+                    match element.hittest(&self.pin_cache, pos) {
+                        HitResult::NoHit => None,
+                        result => Some((element.component, result)),
+                    }
+                });
+
+                match hit {
+                    Some((_, HitResult::HitOutput(pin))) => {
+                        self.wire_start = Some(pin);
+                        (ctx.set_cursor_icon)(CursorIcon::Crosshair);
+                    }
+                    Some((handle, HitResult::Hit)) => {
+                        // Pressing a push button doesn't select or move it, that takes shift
+                        if !self.modifiers.shift() && self.press_button(handle, true) {
+                            self.pressed = Some(handle);
+                            return;
                         }
-                        _ => (),
+
+                        if self.modifiers.shift() {
+                            if !self.selection.remove(&handle) {
+                                self.selection.insert(handle);
+                            }
+                        } else if !self.selection.contains(&handle) {
+                            self.selection.clear();
+                            self.selection.insert(handle);
+                        }
+
+                        self.clicked = Some(handle);
+                        self.moving = self.selection.contains(&handle);
+                        self.move_offset = Vec2::ZERO;
+                        self.moved = Vec2::ZERO;
+                        self.grab_anchor = self
+                            .elements
+                            .iter()
+                            .find(|element| element.component == handle)
+                            .map_or(pos, |element| element.anchor());
+                    }
+                    Some(_) => (),
+                    None => {
+                        if !self.modifiers.shift() {
+                            self.selection.clear();
+                        }
+
+                        self.marquee = Some(pos);
                     }
                 }
             }
@@ -101,19 +752,110 @@ impl ElementManager {
                 button: MouseButton::Left,
                 ..
             } => {
-                let _ = self.grabbed_element.take();
+                self.moving = false;
+
+                if let Some(handle) = self.clicked.take() {
+                    // Clicking an input or switch without moving it flips its value
+                    let click = (self.mouse_position - self.press_position).hypot()
+                        < CLICK_DISTANCE
+                        && !self.modifiers.shift();
+
+                    if let Some(value) = click.then(|| self.sim.toggle_input(handle)).flatten() {
+                        self.sim.tick();
+                        self.history.record(Command::SetInput { handle, value });
+                    } else if let Some(bit) = self.switch_at(handle).filter(|_| click) {
+                        self.execute(Command::FlipSwitch { handle, bit });
+                    } else if self.moved != Vec2::ZERO {
+                        let offset = -self.moved;
+                        self.history.record(Command::Batch(
+                            self.selection
+                                .iter()
+                                .map(|handle| Command::Move {
+                                    handle: *handle,
+                                    offset,
+                                })
+                                .collect(),
+                        ));
+                    }
+                }
+
+                if let Some(start) = self.marquee.take() {
+                    let area = Rect::from_points(start, self.world_mouse_position());
+
+                    self.selection.extend(
+                        self.elements
+                            .iter()
+                            .filter(|element| area.union(element.bounds()) == area)
+                            .map(|element| element.component),
+                    );
+                }
+
+                // Dragged from the palette and dropped onto the canvas
+                if self.placing.is_some() && self.mouse_in_view() {
+                    self.place();
+                }
+
+                if let Some(start) = self.wire_start.take() {
+                    match self.wire_target(start) {
+                        Some((sink, Ok(()))) => self.execute(Command::Connect {
+                            driver: start,
+                            sink,
+                        }),
+                        Some((_, Err(err))) => eprintln!("WARNING: can't connect wire: {err}"),
+                        None => (),
+                    }
+
+                    (ctx.set_cursor_icon)(CursorIcon::Default);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::Delete | VirtualKeyCode::Back),
+                        ..
+                    },
+                ..
+            } => {
+                // Deleting over a connected pin only removes its wires
+                let wires = self.hovered_wires();
+                if !wires.is_empty() {
+                    self.execute(Command::Batch(
+                        wires
+                            .into_iter()
+                            .map(|(driver, sink)| Command::Disconnect { driver, sink })
+                            .collect(),
+                    ));
+                } else if !self.selection.is_empty() {
+                    self.delete_selection();
+                } else if let Some(index) = self.hovered_element() {
+                    let handle = self.elements[index].component;
+                    self.execute(Command::Remove { handle });
+                }
             }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
             WindowEvent::CursorMoved { position, .. } => {
                 self.mouse_position = Vec2::new(position.x, position.y);
 
                 if self.drag {
                     self.translation += self.mouse_position - self.last_mouse_position;
-                } else if let Some(element) = self.grabbed_element {
-                    let index = element.get() - 1;
-
-                    self.elements[index].position +=
+                } else if self.moving {
+                    self.move_offset +=
                         (self.mouse_position - self.last_mouse_position) / self.zoom;
-                    self.elements[index].calculate_positions(&self.sim, &mut self.pin_cache);
+
+                    // The clicked element snaps and the rest of the selection keeps its distance to it
+                    let mut offset = self.move_offset;
+                    if self.snapping() {
+                        offset = self.grid.snap(self.grab_anchor + offset) - self.grab_anchor;
+                    }
+
+                    self.move_selection(offset - self.moved);
+                    self.moved = offset;
+                } else if let Some(start) = self.wire_start {
+                    (ctx.set_cursor_icon)(match self.wire_target(start) {
+                        Some((_, Err(_))) => CursorIcon::NotAllowed,
+                        _ => CursorIcon::Crosshair,
+                    });
                 }
 
                 self.last_mouse_position = self.mouse_position;
@@ -137,12 +879,32 @@ impl ElementManager {
         }
     }
 
-    pub fn draw(&mut self, builder: &mut SceneBuilder, _bounds: &Rect, _mode: i8) {
+    pub fn draw(&mut self, builder: &mut SceneBuilder, bounds: &Rect, _mode: i8) {
+        if self.grid.visible {
+            let mut grid_fragment = SceneFragment::new();
+            self.draw_grid(&mut SceneBuilder::for_fragment(&mut grid_fragment), bounds);
+
+            builder.append(
+                &grid_fragment,
+                Some(Affine::scale(self.zoom).then_translate(self.translation)),
+            );
+        }
+
         let mut elements_fragment = SceneFragment::new();
         let mut elements_builder = SceneBuilder::for_fragment(&mut elements_fragment);
 
         for element in &self.elements {
             element.draw(&mut elements_builder, &mut self.pin_cache, &self.sim);
+
+            if self.selection.contains(&element.component) {
+                elements_builder.stroke(
+                    &Stroke::new(3.0),
+                    Affine::IDENTITY,
+                    &Brush::Solid(SELECTION_COLOR),
+                    None,
+                    &element.bounds().inflate(4.0, 4.0).to_rounded_rect(7.0),
+                );
+            }
         }
 
         builder.append(
@@ -157,45 +919,135 @@ impl ElementManager {
         let mut connection_fragment = SceneFragment::new();
         let mut connection_builder = SceneBuilder::for_fragment(&mut connection_fragment);
 
-        for (p1, p2) in self.sim.in_to_out.iter() {
-            let Some(p1) = self.pin_cache.get(p1) else {
-                eprintln!("Point2 doesn't exist!");
-                continue;
-            };
+        let obstacles = self
+            .elements
+            .iter()
+            .map(|element| element.bounds())
+            .collect::<Vec<_>>();
+        // Taken out for the loop, since the nets borrow the simulator
+        let mut routes = std::mem::take(&mut self.routes);
+        routes.update(&obstacles, self.grid.spacing);
 
-            let Some(p2) = self.pin_cache.get(p2) else {
-                eprintln!("Point2 doesn't exist!");
+        for net in self.sim.nets() {
+            let drivers = net
+                .drivers()
+                .iter()
+                .filter_map(|driver| self.pin_cache.get(driver).copied())
+                .collect::<Vec<_>>();
+            // Pins of components without an element aren't drawn, and neither are their wires
+            let Some(driver_pos) = drivers.first().copied() else {
                 continue;
             };
 
-            let rect = Rect::from_points(*p1, *p2);
-            let cx = 60.0;
-            let cy = 0.0;
+            let width = self.sim.pin_width(net.drivers()[0]);
+            let col = Brush::Solid(state_color(&self.sim.net_value(net, width)));
+            let stroke = Stroke::new(if width == 1 { 2.0 } else { 5.0 });
 
-            let col = Brush::Solid(Color::GREEN);
-            let ctrl1 = Point::new(rect.max_x() - cx, rect.max_y() - cy);
-            let ctrl2 = Point::new(rect.min_x() + cx, rect.min_y() + cy);
+            // Several sinks branch off a shared trunk, and several drivers meet right of the rightmost one.
+            // Either way the wires join at a junction dot.
+            let start = if drivers.len() > 1 || net.sinks().len() > 1 {
+                let junction = if drivers.len() == 1 {
+                    let junction = driver_pos + Vec2::new(JUNCTION_OFFSET, 0.0);
+                    connection_builder.stroke(
+                        &stroke,
+                        Affine::IDENTITY,
+                        &col,
+                        None,
+                        &Line::new(driver_pos, junction),
+                    );
 
-            let (ctrl1, ctrl2) = if p1.y >= p2.y {
-                if ctrl1.x > ctrl2.x {
-                    (Point::new(ctrl2.x, ctrl1.y), Point::new(ctrl1.x, ctrl2.y))
+                    junction
                 } else {
-                    (ctrl1, ctrl2)
-                }
+                    let x = drivers.iter().map(|p| p.x).fold(f64::MIN, f64::max);
+                    let y = drivers.iter().map(|p| p.y).sum::<f64>() / drivers.len() as f64;
+                    let junction = Point::new(x + JUNCTION_OFFSET, y);
+
+                    for driver in &drivers {
+                        let (path, _) = self.wire_path(&mut routes, *driver, junction);
+                        connection_builder.stroke(&stroke, Affine::IDENTITY, &col, None, &path);
+                    }
+
+                    junction
+                };
+
+                connection_builder.fill(
+                    Fill::NonZero,
+                    Affine::IDENTITY,
+                    &col,
+                    None,
+                    &Circle::new(junction, JUNCTION_RADIUS),
+                );
+
+                junction
             } else {
-                if ctrl1.x < ctrl2.x {
-                    (Point::new(ctrl1.x, ctrl2.y), Point::new(ctrl2.x, ctrl1.y))
-                } else {
-                    (ctrl2, ctrl1)
-                }
+                driver_pos
             };
 
+            for sink in net.sinks() {
+                let Some(sink_pos) = self.pin_cache.get(sink).copied() else {
+                    continue;
+                };
+
+                let (path, middle) = self.wire_path(&mut routes, start, sink_pos);
+                connection_builder.stroke(&stroke, Affine::IDENTITY, &col, None, &path);
+
+                if width == 1 {
+                    continue;
+                }
+
+                // Buses are drawn thicker, with a slash and their width at the middle of the wire
+                connection_builder.stroke(
+                    &Stroke::new(2.0),
+                    Affine::IDENTITY,
+                    &col,
+                    None,
+                    &Line::new(middle + Vec2::new(-5.0, 8.0), middle + Vec2::new(5.0, -8.0)),
+                );
+
+                let label = width.to_string();
+                glyphs::draw_text(
+                    &mut connection_builder,
+                    &label,
+                    middle + Vec2::new(-glyphs::text_width(&label, 12.0) / 2.0, -26.0),
+                    12.0,
+                    &col,
+                );
+            }
+        }
+
+        self.routes = routes;
+
+        if let Some(start) = self.wire_start {
+            self.draw_wire_preview(&mut connection_builder, start);
+        }
+
+        if let Some(start) = self.marquee {
+            let area = Rect::from_points(start, self.world_mouse_position());
+
+            connection_builder.fill(
+                Fill::NonZero,
+                Affine::IDENTITY,
+                &Brush::Solid(Color::rgba(1.0, 0.65, 0.0, 0.15)),
+                None,
+                &area,
+            );
             connection_builder.stroke(
-                &Stroke::new(2.0),
+                &Stroke::new(1.0),
                 Affine::IDENTITY,
-                &col,
+                &Brush::Solid(SELECTION_COLOR),
+                None,
+                &area,
+            );
+        }
+
+        if self.placing.is_some() && self.mouse_in_view() {
+            connection_builder.fill(
+                Fill::NonZero,
+                Affine::IDENTITY,
+                &Brush::Solid(Color::rgba(0.2, 0.2, 0.2, 0.4)),
                 None,
-                &CubicBez::new(*p1, ctrl1, ctrl2, *p2),
+                &Rect::from_center_size(self.world_mouse_position(), DEFAULT_SIZE)
+                    .to_rounded_rect(5.0),
             );
         }
 
@@ -204,13 +1056,140 @@ impl ElementManager {
             Some(Affine::scale(self.zoom).then_translate(self.translation)),
         );
     }
+
+    /// Path of a wire from `driver_pos` to `sink_pos` in the current wire style, and its middle point
+    fn wire_path(
+        &self,
+        routes: &mut RouteCache,
+        driver_pos: Point,
+        sink_pos: Point,
+    ) -> (BezPath, Point) {
+        match self.settings.wire_style {
+            WireStyle::Orthogonal => {
+                let points = routes.route(driver_pos, sink_pos);
+
+                let mut path = BezPath::new();
+                path.move_to(points[0]);
+                for point in &points[1..] {
+                    path.line_to(*point);
+                }
+
+                (path, routing::midpoint(points))
+            }
+            WireStyle::Bezier => {
+                let rect = Rect::from_points(sink_pos, driver_pos);
+                let cx = 60.0;
+                let cy = 0.0;
+
+                let ctrl1 = Point::new(rect.max_x() - cx, rect.max_y() - cy);
+                let ctrl2 = Point::new(rect.min_x() + cx, rect.min_y() + cy);
+
+                let (ctrl1, ctrl2) = if sink_pos.y >= driver_pos.y {
+                    if ctrl1.x > ctrl2.x {
+                        (Point::new(ctrl2.x, ctrl1.y), Point::new(ctrl1.x, ctrl2.y))
+                    } else {
+                        (ctrl1, ctrl2)
+                    }
+                } else {
+                    if ctrl1.x < ctrl2.x {
+                        (Point::new(ctrl1.x, ctrl2.y), Point::new(ctrl2.x, ctrl1.y))
+                    } else {
+                        (ctrl2, ctrl1)
+                    }
+                };
+
+                let curve = CubicBez::new(sink_pos, ctrl1, ctrl2, driver_pos);
+                (curve.into_path(0.1), curve.eval(0.5))
+            }
+        }
+    }
+
+    /// Draws the grid lines covering `bounds`. Lines are skipped when zoomed out so they don't get too dense.
+    fn draw_grid(&self, builder: &mut SceneBuilder, bounds: &Rect) {
+        if self.grid.spacing <= 0.0 {
+            return;
+        }
+
+        let mut step = self.grid.spacing;
+        while step * self.zoom < MIN_GRID_SPACING {
+            step *= 5.0;
+        }
+
+        // Visible part of the world
+        let area = Rect::from_points(
+            ((bounds.origin().to_vec2() - self.translation) / self.zoom).to_point(),
+            ((Vec2::new(bounds.x1, bounds.y1) - self.translation) / self.zoom).to_point(),
+        );
+        let stroke = Stroke::new((1.0 / self.zoom) as f32);
+        let brush = |line: f64| {
+            Brush::Solid(if ((line / step).round() as i64).rem_euclid(5) == 0 {
+                GRID_MAJOR_COLOR
+            } else {
+                GRID_COLOR
+            })
+        };
+
+        let mut x = (area.x0 / step).floor() * step;
+        while x <= area.x1 {
+            let line = Line::new((x, area.y0), (x, area.y1));
+            builder.stroke(&stroke, Affine::IDENTITY, &brush(x), None, &line);
+            x += step;
+        }
+
+        let mut y = (area.y0 / step).floor() * step;
+        while y <= area.y1 {
+            let line = Line::new((area.x0, y), (area.x1, y));
+            builder.stroke(&stroke, Affine::IDENTITY, &brush(y), None, &line);
+            y += step;
+        }
+    }
+
+    /// Draws the wire being dragged from `start` to the cursor. It turns red over a pin it can't connect to.
+    fn draw_wire_preview(&self, builder: &mut SceneBuilder, start: RegisteredPin) {
+        let Some(p1) = self.pin_cache.get(&start).copied() else {
+            return;
+        };
+        let p2 = self.world_mouse_position();
+        let target = self.wire_target(start);
+
+        let col = Brush::Solid(match target {
+            Some((_, Ok(()))) => Color::GREEN,
+            Some((_, Err(_))) => Color::RED,
+            None => Color::GRAY,
+        });
+        let width = if self.sim.pin_width(start) == 1 {
+            2.0
+        } else {
+            5.0
+        };
+
+        let cx = ((p2.x - p1.x).abs() / 2.0).min(60.0);
+        builder.stroke(
+            &Stroke::new(width),
+            Affine::IDENTITY,
+            &col,
+            None,
+            &CubicBez::new(p1, p1 + Vec2::new(cx, 0.0), p2 - Vec2::new(cx, 0.0), p2),
+        );
+
+        // Ring around the pin under the cursor
+        if let Some(pos) = target.and_then(|(pin, _)| self.pin_cache.get(&pin)) {
+            builder.stroke(
+                &Stroke::new(2.0),
+                Affine::IDENTITY,
+                &col,
+                None,
+                &Circle::new(*pos, 9.0),
+            );
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum HitResult {
     Hit,
-    HitInput,
-    HitOutput,
+    HitInput(RegisteredPin),
+    HitOutput(RegisteredPin),
     NoHit,
 }
 
@@ -222,6 +1201,10 @@ pub struct Element {
 
     pub position: Point,
     pub size: Size,
+    /// Size asked for when the element was created, which `size` is rounded up from
+    requested_size: Size,
+    /// Distance between neighbouring pins, which is the grid spacing
+    pin_spacing: f64,
 }
 
 impl Element {
@@ -229,34 +1212,86 @@ impl Element {
         Element {
             component,
             position,
-            size: Size::new(100.0, 100.0),
+            size: DEFAULT_SIZE,
+            requested_size: DEFAULT_SIZE,
             input_size: 0,
             output_size: 0,
+            pin_spacing: 0.0,
         }
     }
 
+    /// Laying the element out rounds the size up to whole grid steps, and makes it tall enough for its pins
     pub fn with_size(mut self, size: impl Into<Size>) -> Self {
         self.size = size.into();
+        self.requested_size = self.size;
         self
     }
 
-    pub fn hittest(
-        &self,
-        sim: &Simulator,
-        pin_cache: &HashMap<RegisteredPin, Point>,
-        point: Point,
-    ) -> HitResult {
+    /// Area covered by the body of the element
+    pub fn bounds(&self) -> Rect {
+        Rect::from_origin_size(self.position, self.size)
+    }
+
+    /// Point that's aligned to the grid: the top left corner. Bodies are whole multiples of the grid spacing and pins
+    /// are spaced by it, so every pin lands on the grid along with the corner.
+    fn anchor(&self) -> Point {
+        self.position
+    }
+
+    /// Distance from the top of the `i`th of `pins` pins on one side, counting from 1. The pins are centered,
+    /// rounded to whole grid steps.
+    fn pin_offset(&self, pins: usize, i: usize) -> f64 {
+        let steps = (self.size.height / self.pin_spacing).round() as usize;
+        let first = (steps + 1).saturating_sub(pins) / 2;
+
+        (first + i - 1) as f64 * self.pin_spacing
+    }
+
+    /// Position of the `i`th input pin, counting from 1
+    fn input_position(&self, i: usize) -> Point {
+        Point {
+            x: self.position.x,
+            y: self.position.y + self.pin_offset(self.input_size, i),
+        }
+    }
+
+    /// Position of the `i`th output pin, counting from 1
+    fn output_position(&self, i: usize) -> Point {
+        Point {
+            x: self.position.x + self.size.width,
+            y: self.position.y + self.pin_offset(self.output_size, i),
+        }
+    }
+
+    /// Areas of the switches of a toggle switch or DIP switch, by bit. They sit side by side with the most
+    /// significant bit on the left, in the same order as the value label of a bus.
+    fn switch_bounds(&self, switches: usize) -> Vec<Rect> {
+        let area = self
+            .bounds()
+            .inset(-self.size.width.min(self.size.height) * 0.15);
+        let width = area.width() / switches as f64;
+        let height = area.height().min(width * 2.5);
+
+        (0..switches)
+            .map(|bit| {
+                let center = Point::new(area.x1 - width * (bit as f64 + 0.5), area.center().y);
+                Rect::from_center_size(center, (width * 0.7, height))
+            })
+            .collect()
+    }
+
+    pub fn hittest(&self, pin_cache: &HashMap<RegisteredPin, Point>, point: Point) -> HitResult {
         let inputs = self.input_size;
         {
             for i in 1..inputs + 1 {
-                let Some(pin) = sim.out_to_in.get(&self.component.to_pin(i - 1)) else {
-                    continue;
-                };
-
-                let input_pos = *pin_cache.get(pin).unwrap();
+                let pin = self.component.to_pin(i - 1);
+                let input_pos = pin_cache
+                    .get(&pin)
+                    .copied()
+                    .unwrap_or_else(|| self.input_position(i));
 
                 if Rect::from_center_size(input_pos, (10.0, 10.0)).contains(point) {
-                    return HitResult::HitInput;
+                    return HitResult::HitInput(pin);
                 }
             }
         }
@@ -266,68 +1301,52 @@ impl Element {
 
             for i in 1..outputs + 1 {
                 let pin = self.component.to_pin(i - 1 + inputs); // simulator i/o pins share the same indicies so add input length as offset for outputs
-                let Some(pin) = sim.in_to_out.get(&pin) else {
-                    continue;
-                };
-
-                let output_pos = *pin_cache.get(pin).unwrap();
+                let output_pos = pin_cache
+                    .get(&pin)
+                    .copied()
+                    .unwrap_or_else(|| self.output_position(i));
 
                 if Rect::from_center_size(output_pos, (10.0, 10.0)).contains(point) {
-                    return HitResult::HitOutput;
+                    return HitResult::HitOutput(pin);
                 }
             }
         }
 
-        if Rect::from_origin_size(self.position, self.size).contains(point) {
+        if self.bounds().contains(point) {
             return HitResult::Hit;
         }
 
         HitResult::NoHit
     }
 
+    /// Rounds the requested size up to whole grid steps, at least one step taller than the element has pins on its
+    /// busiest side, and stores where the pins are
     pub fn calculate_positions(
         &mut self,
         sim: &Simulator,
         pin_cache: &mut HashMap<RegisteredPin, Point>,
+        spacing: f64,
     ) {
         let component = sim.get_component(&self.component);
         let component = component.borrow();
-        let inputs = component.input_len();
-        {
-            let calc_input = |i| {
-                let input_offset = self.size.height / (inputs + 1) as f64;
-                Point {
-                    x: self.position.x,
-                    y: self.position.y + input_offset * i as f64,
-                }
-            };
 
-            for i in 1..inputs + 1 {
-                if let Some(pin) = sim.out_to_in.get(&self.component.to_pin(i - 1)) {
-                    pin_cache.insert(*pin, calc_input(i));
-                }
-            }
-        }
-
-        self.input_size = inputs;
+        self.input_size = component.input_len();
         self.output_size = component.output_len();
-        {
-            let outputs = self.output_size;
 
-            let calc_output = |i| {
-                let output_offset = self.size.height / (outputs + 1) as f64;
-                Point {
-                    x: self.position.x + self.size.width,
-                    y: self.position.y + output_offset * i as f64,
-                }
-            };
+        let pins = self.input_size.max(self.output_size);
+        self.pin_spacing = spacing;
+        let steps = |length: f64| (length / spacing).ceil().max(1.0);
+        self.size.width = steps(self.requested_size.width) * spacing;
+        self.size.height = steps(self.requested_size.height).max((pins + 1) as f64) * spacing;
 
-            for i in 1..outputs + 1 {
-                let pin = self.component.to_pin(i - 1 + inputs); // simulator i/o pins share the same indicies so add input length as offset for outputs
-                if let Some(pin) = sim.in_to_out.get(&pin) {
-                    pin_cache.insert(*pin, calc_output(i));
-                }
-            }
+        let inputs = self.input_size;
+        for i in 1..inputs + 1 {
+            pin_cache.insert(self.component.to_pin(i - 1), self.input_position(i));
+        }
+
+        for i in 1..self.output_size + 1 {
+            let pin = self.component.to_pin(i - 1 + inputs); // simulator i/o pins share the same indicies so add input length as offset for outputs
+            pin_cache.insert(pin, self.output_position(i));
         }
     }
 
@@ -346,79 +1365,256 @@ impl Element {
             &RoundedRect::from_origin_size(self.position, (self.size.width, self.size.height), 5.0),
         );
 
-        let inputs = self.input_size;
-
-        {
-            let calc_input = |i| {
-                let input_offset = self.size.height / (inputs + 1) as f64;
-                Point {
-                    x: self.position.x,
-                    y: self.position.y + input_offset * i as f64,
-                }
-            };
+        // Inputs, outputs and constants show their current value
+        let label = match &*sim.get_component(&self.component).borrow() {
+            Component::Input(value) | Component::Output(value) => Some(value.to_string()),
+            Component::Constant(value) => Some(StateVec::from(*value).to_string()),
+            _ => None,
+        };
+        if let Some(label) = label {
+            let height = (self.size.height * 0.4)
+                .min(self.size.width * 0.8 / glyphs::text_width(&label, 1.0));
+            let origin = self.position
+                + (self.size.to_vec2() - Vec2::new(glyphs::text_width(&label, height), height))
+                    / 2.0;
 
-            for i in 1..inputs + 1 {
-                if let Some(pin) = sim.out_to_in.get(&self.component.to_pin(i - 1)) {
-                    let input_pos = *pin_cache.entry(*pin).or_insert_with(|| calc_input(i));
+            glyphs::draw_text(builder, &label, origin, height, &Brush::Solid(Color::WHITE));
+        }
 
-                    // Pin is connected
-                    builder.fill(
-                        Fill::NonZero,
-                        Affine::IDENTITY,
-                        &Brush::Solid(Color::GREEN),
-                        None,
-                        &Circle::new(input_pos, 5.0),
-                    );
-                } else {
-                    let input_pos = calc_input(i);
-                    // Pin is not connected
-                    builder.stroke(
-                        &Stroke::new(2.0),
-                        Affine::IDENTITY,
-                        &Brush::Solid(Color::GREEN),
-                        None,
-                        &Circle::new(input_pos, 5.0),
-                    );
+        match &*sim.get_component(&self.component).borrow() {
+            Component::Led(value) => builder.fill(
+                Fill::NonZero,
+                Affine::IDENTITY,
+                &Brush::Solid(indicator_color(*value)),
+                None,
+                &Circle::new(
+                    self.bounds().center(),
+                    self.size.width.min(self.size.height) * 0.3,
+                ),
+            ),
+            Component::SevenSegment(value) => {
+                let colors =
+                    [0, 1, 2, 3, 4, 5, 6].map(|segment| indicator_color(value.bit(segment)));
+                draw_segments(builder, self.bounds(), colors);
+            }
+            Component::HexDisplay(value) => {
+                // Digits that can't be shown get a dash in the color of the value
+                let colors = match value.to_number() {
+                    Some(digit) => [0, 1, 2, 3, 4, 5, 6].map(|segment| {
+                        if HEX_SEGMENTS[digit as usize] & 1 << segment != 0 {
+                            LIT_COLOR
+                        } else {
+                            UNLIT_COLOR
+                        }
+                    }),
+                    None => {
+                        let mut colors = [UNLIT_COLOR; 7];
+                        colors[6] = state_color(value);
+                        colors
+                    }
+                };
+                draw_segments(builder, self.bounds(), colors);
+            }
+            Component::PushButton(value) => builder.fill(
+                Fill::NonZero,
+                Affine::IDENTITY,
+                &Brush::Solid(match value {
+                    State::High => HIGH_COLOR,
+                    _ => BUTTON_COLOR,
+                }),
+                None,
+                &Circle::new(
+                    self.bounds().center(),
+                    self.size.width.min(self.size.height) * 0.3,
+                ),
+            ),
+            Component::ToggleSwitch(value) => {
+                draw_switch(builder, self.switch_bounds(1)[0], *value);
+            }
+            Component::DipSwitch(value) => {
+                for (bounds, bit) in self
+                    .switch_bounds(value.width())
+                    .into_iter()
+                    .zip(value.bits())
+                {
+                    draw_switch(builder, bounds, *bit);
                 }
             }
+            _ => {}
         }
 
-        {
-            let outputs = self.output_size;
+        let inputs = self.input_size;
 
-            let calc_output = |i| {
-                let output_offset = self.size.height / (outputs + 1) as f64;
-                Point {
-                    x: self.position.x + self.size.width,
-                    y: self.position.y + output_offset * i as f64,
-                }
-            };
+        for i in 1..inputs + 1 {
+            let pin = self.component.to_pin(i - 1);
+            let input_pos = *pin_cache
+                .entry(pin)
+                .or_insert_with(|| self.input_position(i));
 
-            for i in 1..outputs + 1 {
-                let pin = self.component.to_pin(i - 1 + inputs); // simulator i/o pins share the same indicies so add input length as offset for outputs
-                if let Some(pin) = sim.in_to_out.get(&pin) {
-                    let output_pos = *pin_cache.entry(*pin).or_insert_with(|| calc_output(i));
+            let value = sim
+                .net_of(pin)
+                .is_some()
+                .then(|| sim.input_state(pin, sim.pin_width(pin)));
+            draw_pin(builder, input_pos, value.as_ref());
+        }
 
-                    // Pin is connected
-                    builder.fill(
-                        Fill::NonZero,
-                        Affine::IDENTITY,
-                        &Brush::Solid(Color::GREEN),
-                        None,
-                        &Circle::new(output_pos, 5.0),
-                    );
-                } else {
-                    let output_pos = calc_output(i);
-                    // Pin is not connected
-                    builder.stroke(
-                        &Stroke::new(2.0),
-                        Affine::IDENTITY,
-                        &Brush::Solid(Color::GREEN),
-                        None,
-                        &Circle::new(output_pos, 5.0),
-                    );
-                }
-            }
+        for i in 1..self.output_size + 1 {
+            let pin = self.component.to_pin(i - 1 + inputs); // simulator i/o pins share the same indicies so add input length as offset for outputs
+            let output_pos = *pin_cache
+                .entry(pin)
+                .or_insert_with(|| self.output_position(i));
+
+            let value = sim.net_of(pin).is_some().then(|| sim.get_pin(pin));
+            draw_pin(builder, output_pos, value.as_ref());
+        }
+    }
+}
+
+/// Color of a wire or pin carrying `value`. Buses get their own color unless all of their bits are invalid or
+/// floating.
+fn state_color(value: &StateVec) -> Color {
+    if value.width() > 1 {
+        if value.bits().iter().all(|bit| *bit == State::Invalid) {
+            return INVALID_COLOR;
         }
+        if value.bits().iter().all(|bit| *bit == State::HighZ) {
+            return HIGHZ_COLOR;
+        }
+
+        return BUS_COLOR;
+    }
+
+    match value.bit(0) {
+        State::Low => LOW_COLOR,
+        State::High => HIGH_COLOR,
+        State::Invalid => INVALID_COLOR,
+        State::HighZ => HIGHZ_COLOR,
+    }
+}
+
+/// Color of an LED or display segment driven by `state`. Values other than high and low get the wire colors.
+fn indicator_color(state: State) -> Color {
+    match state {
+        State::Low => UNLIT_COLOR,
+        State::High => LIT_COLOR,
+        State::Invalid => INVALID_COLOR,
+        State::HighZ => HIGHZ_COLOR,
+    }
+}
+
+/// Draws a 7-segment digit as large as fits in `bounds`, with segments a to g in the given colors
+fn draw_segments(builder: &mut SceneBuilder, bounds: Rect, colors: [Color; 7]) {
+    let area = bounds.inset(-bounds.width().min(bounds.height()) * 0.15);
+    let height = area.height().min(area.width() * 2.0);
+    let digit = Rect::from_center_size(area.center(), (height / 2.0, height));
+    let thickness = digit.width() * 0.18;
+
+    let (left, right, top, bottom) = (digit.x0, digit.x1, digit.y0, digit.y1);
+    let middle = digit.center().y;
+    let half = thickness / 2.0;
+    let segments = [
+        Rect::new(left + thickness, top, right - thickness, top + thickness),
+        Rect::new(right - thickness, top + thickness, right, middle - half),
+        Rect::new(right - thickness, middle + half, right, bottom - thickness),
+        Rect::new(
+            left + thickness,
+            bottom - thickness,
+            right - thickness,
+            bottom,
+        ),
+        Rect::new(left, middle + half, left + thickness, bottom - thickness),
+        Rect::new(left, top + thickness, left + thickness, middle - half),
+        Rect::new(
+            left + thickness,
+            middle - half,
+            right - thickness,
+            middle + half,
+        ),
+    ];
+
+    for (segment, color) in segments.into_iter().zip(colors) {
+        builder.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            &Brush::Solid(color),
+            None,
+            &segment.inset(-1.0).to_rounded_rect(half),
+        );
+    }
+}
+
+/// Draws a switch as a slot with the knob at the top while it's on
+fn draw_switch(builder: &mut SceneBuilder, bounds: Rect, value: State) {
+    builder.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        &Brush::Solid(SWITCH_SLOT_COLOR),
+        None,
+        &bounds.to_rounded_rect(2.0),
+    );
+
+    let knob_height = bounds.height() / 2.0;
+    let knob = match value {
+        State::High => Rect::new(bounds.x0, bounds.y0, bounds.x1, bounds.y0 + knob_height),
+        _ => Rect::new(bounds.x0, bounds.y1 - knob_height, bounds.x1, bounds.y1),
+    };
+    builder.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        &Brush::Solid(SWITCH_KNOB_COLOR),
+        None,
+        &knob.inset(-2.0).to_rounded_rect(2.0),
+    );
+}
+
+/// Draws a pin filled with the color of its value, or as an outline if it isn't connected
+fn draw_pin(builder: &mut SceneBuilder, position: Point, value: Option<&StateVec>) {
+    match value {
+        Some(value) => builder.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            &Brush::Solid(state_color(value)),
+            None,
+            &Circle::new(position, 5.0),
+        ),
+        None => builder.stroke(
+            &Stroke::new(2.0),
+            Affine::IDENTITY,
+            &Brush::Solid(UNCONNECTED_COLOR),
+            None,
+            &Circle::new(position, 5.0),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{State::*, Storage};
+
+    #[test]
+    fn test_push_button_while_paused() {
+        let mut sim = Simulator::new();
+
+        let d = sim.insert_component(Component::Constant(High));
+        let button = sim.insert_component(Component::PushButton(Low));
+        let flip_flop = sim.insert_component(Component::DFlipFlop(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(d, 0), RegisteredPin(flip_flop, 0));
+        sim.connect(RegisteredPin(button, 0), RegisteredPin(flip_flop, 1));
+        sim.connect(RegisteredPin(flip_flop, 2), RegisteredPin(q, 0));
+        sim.initialize();
+
+        let mut em = ElementManager::new(sim, (800.0, 600.0));
+        assert!(!em.running);
+        assert_eq!(em.sim().inspect_pin(RegisteredPin(q, 0)), Low);
+
+        // The press clocks the flip-flop before the release can take it back
+        assert!(em.press_button(ComponentHandle(button), true));
+        assert!(em.press_button(ComponentHandle(button), false));
+        assert_eq!(em.sim().inspect_pin(RegisteredPin(q, 0)), High);
+
+        assert!(!em.press_button(ComponentHandle(d), true));
     }
 }
@@ -0,0 +1,95 @@
+//! Seven segment style glyphs for short labels like bus widths. The renderer has no text support, so
+//! characters are drawn as strokes.
+
+use vello::{
+    kurbo::{Affine, Line, Point, Rect},
+    peniko::{Brush, Stroke},
+    SceneBuilder,
+};
+
+/// Width of a glyph relative to its height
+const ASPECT: f64 = 0.5;
+/// Space between glyphs relative to the height
+const SPACING: f64 = 0.25;
+
+/// Segments lit for a character, bit 0 is segment `a` through bit 6 for segment `g`
+pub fn segments_for(c: char) -> Option<u8> {
+    Some(match c.to_ascii_uppercase() {
+        '0' => 0b0111111,
+        '1' => 0b0000110,
+        '2' => 0b1011011,
+        '3' => 0b1001111,
+        '4' => 0b1100110,
+        '5' => 0b1101101,
+        '6' => 0b1111101,
+        '7' => 0b0000111,
+        '8' => 0b1111111,
+        '9' => 0b1101111,
+        'A' => 0b1110111,
+        'B' => 0b1111100,
+        'C' => 0b0111001,
+        'D' => 0b1011110,
+        'E' => 0b1111001,
+        'F' => 0b1110001,
+        'X' => 0b1110110,
+        '-' => 0b1000000,
+        ' ' => 0,
+        _ => return None,
+    })
+}
+
+/// Line of a segment (0 = `a` through 6 = `g`) inside the bounds of a glyph
+pub fn segment_line(segment: usize, bounds: Rect) -> Line {
+    let (x0, x1) = (bounds.x0, bounds.x1);
+    let (y0, y1) = (bounds.y0, bounds.y1);
+    let ym = bounds.center().y;
+
+    let (p0, p1) = match segment {
+        0 => ((x0, y0), (x1, y0)),
+        1 => ((x1, y0), (x1, ym)),
+        2 => ((x1, ym), (x1, y1)),
+        3 => ((x0, y1), (x1, y1)),
+        4 => ((x0, ym), (x0, y1)),
+        5 => ((x0, y0), (x0, ym)),
+        6 => ((x0, ym), (x1, ym)),
+        _ => panic!("seven segment glyphs only have 7 segments"),
+    };
+
+    Line::new(p0, p1)
+}
+
+/// Width taken up by `text` when drawn at the given height
+pub fn text_width(text: &str, height: f64) -> f64 {
+    let count = text.chars().count() as f64;
+    (count * (ASPECT + SPACING) - SPACING).max(0.0) * height
+}
+
+/// Draws `text` with its top left corner at `origin`. Characters without a glyph are skipped.
+pub fn draw_text(
+    builder: &mut SceneBuilder,
+    text: &str,
+    origin: Point,
+    height: f64,
+    brush: &Brush,
+) {
+    let stroke = Stroke::new((height / 8.0) as f32);
+
+    for (i, c) in text.chars().enumerate() {
+        let Some(segments) = segments_for(c) else {
+            continue;
+        };
+
+        let x = origin.x + i as f64 * (ASPECT + SPACING) * height;
+        let bounds = Rect::new(x, origin.y, x + ASPECT * height, origin.y + height);
+
+        for segment in (0..7).filter(|segment| segments & (1 << segment) != 0) {
+            builder.stroke(
+                &stroke,
+                Affine::IDENTITY,
+                brush,
+                None,
+                &segment_line(segment, bounds),
+            );
+        }
+    }
+}
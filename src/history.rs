@@ -0,0 +1,105 @@
+//! Undo/redo history of editor operations. Each entry is the command that reverts an operation; applying it
+//! with `ElementManager` hands back the command that redoes the operation.
+
+use vello::kurbo::Vec2;
+
+use crate::{element::Element, Component, ComponentHandle, RegisteredPin, StateVec};
+
+pub enum Command {
+    /// Removes a component along with its element and wires
+    Remove { handle: ComponentHandle },
+    /// Puts a removed component back into its old slot, with its element and wires
+    Restore {
+        handle: ComponentHandle,
+        component: Component,
+        /// Element and its place in the drawing order
+        element: Option<(usize, Element)>,
+        connections: Vec<(RegisteredPin, RegisteredPin)>,
+    },
+    /// Moves the element of a component
+    Move {
+        handle: ComponentHandle,
+        offset: Vec2,
+    },
+    Connect {
+        driver: RegisteredPin,
+        sink: RegisteredPin,
+    },
+    Disconnect {
+        driver: RegisteredPin,
+        sink: RegisteredPin,
+    },
+    /// Gives an input a value. Toggling is undone this way, since inverting an invalid bit twice makes it low.
+    SetInput {
+        handle: ComponentHandle,
+        value: StateVec,
+    },
+    /// Flips a toggle switch, or one bit of a DIP switch
+    FlipSwitch { handle: ComponentHandle, bit: usize },
+    /// Several commands that are undone together, like moving a group of elements
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    /// Whether the command changes which components and wires make up the circuit, rather than values or positions
+    pub fn is_structural(&self) -> bool {
+        match self {
+            Command::Remove { .. }
+            | Command::Restore { .. }
+            | Command::Connect { .. }
+            | Command::Disconnect { .. } => true,
+            Command::Move { .. } | Command::SetInput { .. } | Command::FlipSwitch { .. } => false,
+            Command::Batch(commands) => commands.iter().any(Command::is_structural),
+        }
+    }
+
+    /// Whether the command changes values in the circuit that have to propagate, unlike moving elements around
+    pub fn changes_values(&self) -> bool {
+        match self {
+            Command::SetInput { .. } | Command::FlipSwitch { .. } => true,
+            Command::Remove { .. }
+            | Command::Restore { .. }
+            | Command::Move { .. }
+            | Command::Connect { .. }
+            | Command::Disconnect { .. } => false,
+            Command::Batch(commands) => commands.iter().any(Command::changes_values),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    /// Oldest entries are dropped past this many
+    const LIMIT: usize = 1000;
+
+    /// Records how to revert an operation that was just done. Whatever could be redone is dropped.
+    pub fn record(&mut self, undo: Command) {
+        self.redo.clear();
+        self.push_undo(undo);
+    }
+
+    pub fn push_undo(&mut self, undo: Command) {
+        self.undo.push(undo);
+
+        if self.undo.len() > Self::LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn push_redo(&mut self, redo: Command) {
+        self.redo.push(redo);
+    }
+
+    pub fn take_undo(&mut self) -> Option<Command> {
+        self.undo.pop()
+    }
+
+    pub fn take_redo(&mut self) -> Option<Command> {
+        self.redo.pop()
+    }
+}
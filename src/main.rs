@@ -1,29 +1,39 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::{Debug, Display},
+    ops::RangeInclusive,
+    path::PathBuf,
     rc::Rc,
-    sync::Arc,
 };
 
 mod element;
+mod glyphs;
+mod history;
+mod memory;
 mod messages;
+mod palette;
 mod platform;
+mod project;
 mod render;
+mod routing;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum State {
     Low,
     High,
     Invalid,
+    /// High impedance: nothing is driving the wire
+    HighZ,
 }
 
 use element::{Element, ElementManager};
+use memory::Memory;
 use messages::Message;
 use render::RenderManager;
 use winit::{
     dpi::LogicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoopBuilder},
     window::WindowBuilder,
 };
@@ -47,84 +57,61 @@ async fn main() {
         .build(&event_loop)
         .unwrap();
 
-    let mut sim = Arc::new(Simulator::new());
-    let mut elements = Vec::new();
-
-    {
-        let sim = Arc::get_mut(&mut sim).unwrap();
-        let in1 = sim.insert_component(Component::Input(High));
-        let in2 = sim.insert_component(Component::Input(Low));
-
-        let and_index = sim.insert_component(Component::XorGate);
-        let custom_index = sim.insert_component(Component::Custom(16));
-
-        let outputs = (0..16)
-            .map(|_| sim.insert_component(Component::Output(Low)))
-            .collect::<Vec<_>>();
-
-        elements.push(Element::new(ComponentHandle(in1), (100.0, 100.0).into()));
-
-        elements.push(Element::new(ComponentHandle(in2), (100.0, 400.0).into()));
-
-        elements.push(Element::new(
-            ComponentHandle(and_index),
-            (300.0, 250.0).into(),
-        ));
-
-        elements.push(
-            Element::new(ComponentHandle(custom_index), (500.0, 250.0).into())
-                .with_size((100.0, 200.0)),
-        );
-
-        sim.connect(RegisteredPin(in1, 0), RegisteredPin(and_index, 0));
-        sim.connect(RegisteredPin(in2, 0), RegisteredPin(and_index, 1));
-        // sim.connect(RegisteredPin(and_index, 2), RegisteredPin(output_index, 0));
-
-        outputs.into_iter().enumerate().for_each(|out| {
-            elements.push(Element::new(
-                ComponentHandle(out.1),
-                (
-                    700.0,
-                    250.0 - 16.0 / 2.0 * (100.0 + 20.0) + out.0 as f64 * 120.0,
-                )
-                    .into(),
-            ));
-
-            sim.connect(RegisteredPin(custom_index, out.0), RegisteredPin(out.1, 0))
-        });
-
-        sim.tick();
-    }
+    // The project to open can be passed on the command line, saving writes back to it
+    let project_path = std::env::args()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("circuit.json"), PathBuf::from);
+
+    let (mut sim, elements, settings) = if project_path.exists() {
+        match project::load(&project_path) {
+            Ok(project) => project,
+            Err(err) => {
+                eprintln!("ERROR: couldn't load '{}': {err}", project_path.display());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let (sim, elements) = demo_circuit();
+        (sim, elements, project::Settings::default())
+    };
+    sim.initialize();
 
     let mut egui_state = egui_winit::State::new(&window);
     let egui_context = egui::Context::default();
     egui_extras::install_image_loaders(&egui_context);
 
     let element_manager = ElementManager::new(
-        sim.clone(),
+        sim,
         (
             window.inner_size().width as f64,
             window.inner_size().height as f64,
         ),
     )
-    .with_elements(elements);
+    .with_elements(elements)
+    .with_settings(settings);
 
     let mut render_manager = RenderManager::new(&window, element_manager).await;
+    let mut modifiers = ModifiersState::empty();
 
     event_loop.run(move |event, _, cf| {
         match event {
             Event::LoopDestroyed => *cf = ControlFlow::Exit,
             Event::WindowEvent { event, .. } => {
-                egui_state.on_event(&egui_context, &event);
-
-                render_manager.element_manager.event(
-                    &EventContext {
-                        set_cursor_icon: &|icon| {
-                            window.set_cursor_icon(icon);
+                let response = egui_state.on_event(&egui_context, &event);
+                // Keys typed into a text field are meant for egui, not the canvas
+                let typing =
+                    response.consumed && matches!(event, WindowEvent::KeyboardInput { .. });
+
+                if !typing {
+                    render_manager.element_manager.event(
+                        &EventContext {
+                            set_cursor_icon: &|icon| {
+                                window.set_cursor_icon(icon);
+                            },
                         },
-                    },
-                    &event,
-                );
+                        &event,
+                    );
+                }
 
                 match &event {
                     WindowEvent::CloseRequested => *cf = ControlFlow::Exit,
@@ -137,12 +124,56 @@ async fn main() {
                             },
                         ..
                     } => *cf = ControlFlow::Exit,
+                    WindowEvent::ModifiersChanged(state) => modifiers = *state,
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::S),
+                                ..
+                            },
+                        ..
+                    } if modifiers.ctrl() || modifiers.logo() => {
+                        let element_manager = &render_manager.element_manager;
+                        match project::save(
+                            &project_path,
+                            element_manager.sim(),
+                            &element_manager.elements,
+                            &element_manager.settings,
+                        ) {
+                            Ok(()) => println!("Saved to '{}'", project_path.display()),
+                            Err(err) => eprintln!(
+                                "ERROR: couldn't save '{}': {err}",
+                                project_path.display()
+                            ),
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Z),
+                                ..
+                            },
+                        ..
+                    } if (modifiers.ctrl() || modifiers.logo()) && !typing => {
+                        if modifiers.shift() {
+                            render_manager.element_manager.redo();
+                        } else {
+                            render_manager.element_manager.undo();
+                        }
+                    }
                     WindowEvent::Resized(size) => render_manager.resize(size.width, size.height),
                     _ => (),
                 }
                 window.request_redraw()
             }
             Event::RedrawRequested(_) => {
+                // Keep redrawing while time is running so clocks animate
+                if render_manager.element_manager.update() {
+                    window.request_redraw();
+                }
+
                 render_manager.draw();
                 render_manager.update_gui(&mut egui_state, &egui_context, &window);
                 render_manager.present();
@@ -152,19 +183,254 @@ async fn main() {
     });
 }
 
+/// Circuit shown when there's no project to open
+fn demo_circuit() -> (Simulator, Vec<Element>) {
+    let mut sim = Simulator::new();
+    let mut elements = Vec::new();
+
+    let in1 = sim.insert_component(Component::Input(High.into()));
+    let in2 = sim.insert_component(Component::Input(Low.into()));
+    let in3 = sim.insert_component(Component::Input(High.into()));
+
+    let and_index = sim.insert_component(Component::XorGate(2));
+    let custom_index = sim.insert_component(Component::Custom(Box::new(nand_gate())));
+    let output_index = sim.insert_component(Component::Output(Low.into()));
+
+    elements.push(Element::new(ComponentHandle(in1), (100.0, 100.0).into()));
+
+    elements.push(Element::new(ComponentHandle(in2), (100.0, 400.0).into()));
+
+    elements.push(Element::new(ComponentHandle(in3), (300.0, 450.0).into()));
+
+    elements.push(Element::new(
+        ComponentHandle(and_index),
+        (300.0, 250.0).into(),
+    ));
+
+    elements.push(Element::new(
+        ComponentHandle(custom_index),
+        (500.0, 250.0).into(),
+    ));
+
+    elements.push(Element::new(
+        ComponentHandle(output_index),
+        (700.0, 250.0).into(),
+    ));
+
+    sim.connect(RegisteredPin(in1, 0), RegisteredPin(and_index, 0));
+    sim.connect(RegisteredPin(in2, 0), RegisteredPin(and_index, 1));
+    sim.connect(RegisteredPin(and_index, 2), RegisteredPin(custom_index, 0));
+    sim.connect(RegisteredPin(in3, 0), RegisteredPin(custom_index, 1));
+    sim.connect(
+        RegisteredPin(custom_index, 2),
+        RegisteredPin(output_index, 0),
+    );
+
+    // 4 bit bus
+    let merger_index = sim.insert_component(Component::Merger(4));
+    let bus_output = sim.insert_component(Component::Output(StateVec::new(4, Low)));
+
+    for bit in 0..4 {
+        let state = if bit % 2 == 0 { High } else { Low };
+        let input = sim.insert_component(Component::Input(state.into()));
+
+        elements.push(Element::new(
+            ComponentHandle(input),
+            (100.0, 600.0 + bit as f64 * 120.0).into(),
+        ));
+
+        sim.connect(RegisteredPin(input, 0), RegisteredPin(merger_index, bit));
+    }
+
+    elements.push(
+        Element::new(ComponentHandle(merger_index), (300.0, 600.0).into()).with_size((60.0, 460.0)),
+    );
+
+    elements.push(Element::new(
+        ComponentHandle(bus_output),
+        (500.0, 780.0).into(),
+    ));
+
+    sim.connect(RegisteredPin(merger_index, 4), RegisteredPin(bus_output, 0));
+
+    (sim, elements)
+}
+
+/// Builds a two input nand gate out of primitive gates
+fn nand_gate() -> Subcircuit {
+    let mut sim = Simulator::new();
+
+    let a = sim.insert_component(Component::Input(Low.into()));
+    let b = sim.insert_component(Component::Input(Low.into()));
+    let and_index = sim.insert_component(Component::AndGate(2));
+    let not_index = sim.insert_component(Component::NotGate);
+    let y = sim.insert_component(Component::Output(Low.into()));
+
+    sim.connect(RegisteredPin(a, 0), RegisteredPin(and_index, 0));
+    sim.connect(RegisteredPin(b, 0), RegisteredPin(and_index, 1));
+    sim.connect(RegisteredPin(and_index, 2), RegisteredPin(not_index, 0));
+    sim.connect(RegisteredPin(not_index, 1), RegisteredPin(y, 0));
+
+    Subcircuit::new("nand", sim)
+        .with_input("a", a)
+        .with_input("b", b)
+        .with_output("y", y)
+}
+
 impl Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         <State as Debug>::fmt(self, f)
     }
 }
 
-struct SimParams<'a> {
-    input: RegisteredPin,
-    output: RegisteredPin,
-    sim: &'a Simulator,
-    pin_cache: &'a mut HashMap<RegisteredPin, State>,
+/// Value carried by a pin. Single wires carry one bit, buses carry several, least significant bit first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StateVec(Vec<State>);
+
+impl StateVec {
+    pub fn new(width: usize, state: State) -> StateVec {
+        StateVec(vec![state; width])
+    }
+
+    pub fn width(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn bit(&self, index: usize) -> State {
+        self.0.get(index).copied().unwrap_or(Invalid)
+    }
+
+    pub fn bits(&self) -> &[State] {
+        &self.0
+    }
+
+    /// Value as an unsigned number, or `None` if any bit isn't high or low
+    pub fn to_number(&self) -> Option<u64> {
+        self.0
+            .iter()
+            .enumerate()
+            .try_fold(0, |number, (index, bit)| match bit {
+                Low => Some(number),
+                High => Some(number | 1 << index),
+                Invalid | HighZ => None,
+            })
+    }
+
+    /// Lowest `width` bits of a number
+    pub fn from_number(number: u64, width: usize) -> StateVec {
+        (0..width)
+            .map(|index| {
+                if index < 64 && number >> index & 1 == 1 {
+                    High
+                } else {
+                    Low
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<State> for StateVec {
+    fn from(value: State) -> Self {
+        StateVec(vec![value])
+    }
+}
+
+impl From<Vec<State>> for StateVec {
+    fn from(value: Vec<State>) -> Self {
+        StateVec(value)
+    }
+}
+
+impl FromIterator<State> for StateVec {
+    fn from_iter<T: IntoIterator<Item = State>>(iter: T) -> Self {
+        StateVec(iter.into_iter().collect())
+    }
+}
+
+/// Single bit values compare equal to the `State` they carry
+impl PartialEq<State> for StateVec {
+    fn eq(&self, other: &State) -> bool {
+        self.0.as_slice() == [*other]
+    }
+}
+
+impl Display for StateVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Most significant bit first, like a written binary number
+        for bit in self.0.iter().rev() {
+            f.write_str(match bit {
+                Low => "0",
+                High => "1",
+                Invalid => "X",
+                HighZ => "Z",
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Simulation time, measured in abstract gate delay units
+pub type SimTime = u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    /// The event queue was still busy when the step budget ran out, usually because the circuit oscillates
+    Unsettled { time: SimTime },
+    /// A feedback loop kept changing instead of settling to a stable value
+    Oscillation { components: Vec<ComponentHandle> },
+}
+
+impl Display for SimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimError::Unsettled { time } => {
+                write!(f, "simulation didn't settle (stopped at t = {time})")
+            }
+            SimError::Oscillation { components } => {
+                write!(f, "feedback loop through {components:?} oscillates")
+            }
+        }
+    }
+}
+
+/// Reasons a wire can't be connected between two pins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionError {
+    /// The wire doesn't start at an output pin
+    NotAnOutput,
+    /// The wire doesn't end at an input pin
+    NotAnInput,
+    /// The pins are already joined by a wire
+    AlreadyConnected,
+    /// The input pin already has a driver, and they can't all float to share the net
+    AlreadyDriven,
+    /// The pins carry a different number of bits
+    WidthMismatch { driver: usize, sink: usize },
+}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionError::NotAnOutput => write!(f, "wires have to start at an output pin"),
+            ConnectionError::NotAnInput => write!(f, "wires have to end at an input pin"),
+            ConnectionError::AlreadyConnected => write!(f, "pins are already connected"),
+            ConnectionError::AlreadyDriven => write!(
+                f,
+                "input pin is already driven, only tri-state outputs can share a net"
+            ),
+            ConnectionError::WidthMismatch { driver, sink } => write!(
+                f,
+                "can't connect a {driver} bit output to a {sink} bit input"
+            ),
+        }
+    }
 }
 
+/// Identifies a component by its slot in the simulator. Slots are never reused: new components are always appended
+/// and removing one leaves a tombstone behind, so a handle can only ever resolve to the component it was made for.
+/// Undoing a removal puts the component back into its old slot, which keeps the handles in the history valid.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ComponentHandle(usize);
 
@@ -174,69 +440,321 @@ impl ComponentHandle {
     }
 }
 
+#[derive(Clone)]
 enum Component {
-    Input(State),
-    Output(State),
-
-    OrGate,
-    AndGate,
-    XorGate,
+    Input(StateVec),
+    Output(StateVec),
+    /// High only while it's held down with the mouse
+    PushButton(State),
+    /// Flips between low and high when clicked
+    ToggleSwitch(State),
+    /// Row of switches driving a bus, one per bit
+    DipSwitch(StateVec),
+    /// Always outputs the same level, to tie off inputs
+    Constant(State),
+
+    /// Gates with the given number of inputs, between 2 and 32. Xor gates output high for an odd number of
+    /// high inputs.
+    OrGate(usize),
+    AndGate(usize),
+    XorGate(usize),
+    NandGate(usize),
+    NorGate(usize),
+    XnorGate(usize),
     NotGate,
-    Custom(usize),
+    BufferGate,
+    /// Splits a bus of the given width into single bit outputs, least significant bit first
+    Splitter(usize),
+    /// Joins single bit inputs into a bus of the given width, least significant bit first
+    Merger(usize),
+    /// Passes a value of the given width through while enabled and lets go of the wire otherwise.
+    /// Pins are data, enable and output.
+    TriState(usize),
+    Clock(Clock),
+    /// Stores D on the rising edge of the clock. Pins are D, clock, Q and not Q.
+    DFlipFlop(Storage),
+    /// Sets on J, resets on K and toggles on both at the rising edge of the clock.
+    /// Pins are J, K, clock, Q and not Q.
+    JkFlipFlop(Storage),
+    /// Toggles when T is high at the rising edge of the clock. Pins are T, clock, Q and not Q.
+    TFlipFlop(Storage),
+    /// Set and reset latch. Pins are S, R, Q and not Q.
+    SrLatch(Storage),
+    /// Follows D while enabled and holds its value otherwise. Pins are D, enable, Q and not Q.
+    DLatch(Storage),
+    /// Stores a value of the width of its storage on the rising edge of the clock while enabled. Reset clears it
+    /// right away, without waiting for the clock. Pins are D, clock, enable, reset and Q.
+    Register(Storage),
+    /// Passes one of `2^select` data inputs through, picked by a `select` bit bus. Pins are the data inputs in
+    /// order, select and output. Data pins and the output carry `width` bits.
+    Multiplexer {
+        select: usize,
+        width: usize,
+    },
+    /// Sends its data input to one of `2^select` outputs, picked by a `select` bit bus. The other outputs are low.
+    /// Pins are data, select and the outputs in order. Data pins carry `width` bits.
+    Demultiplexer {
+        select: usize,
+        width: usize,
+    },
+    /// Raises the one of its `2^select` outputs picked by a `select` bit bus. Pins are select and the outputs in
+    /// order.
+    Decoder(usize),
+    /// Outputs the index of the highest of its `2^select` inputs that's high, as a `select` bit bus, and whether
+    /// any input is high at all. Pins are the inputs in order, index and valid.
+    PriorityEncoder(usize),
+    /// Adds two numbers of the given width. Pins are A, B, carry in, sum and carry out.
+    Adder(usize),
+    /// Subtracts B from A. Pins are A, B, borrow in, difference and borrow out.
+    Subtractor(usize),
+    /// Compares two unsigned numbers. Pins are A, B, less than, equal and greater than.
+    Comparator(usize),
+    /// Multiplies two numbers into a product twice as wide. Pins are A, B and product.
+    Multiplier(usize),
+    /// Read-only memory. Pins are address and data.
+    Rom(Memory),
+    /// Stores data at the address on the rising edge of the clock while write is enabled. Reads don't wait for the
+    /// clock. Pins are address, data in, write enable, clock and data out.
+    Ram(Memory),
+    /// Lights up while its input is high
+    Led(State),
+    /// Digit made of seven segments, each lit while its input is high. Pins are segments a to g, clockwise from
+    /// the top with g in the middle. The value holds them in the same order.
+    SevenSegment(StateVec),
+    /// Shows the value of a 4 bit bus as a hexadecimal digit
+    HexDisplay(StateVec),
+    Custom(Box<Subcircuit>),
+    /// Left behind by `Simulator::remove_component`, so the handles of the other components stay valid
+    Removed,
 }
 
 impl Component {
-    pub const fn input_len(&self) -> usize {
+    /// Number of inputs gates can have
+    pub const GATE_INPUTS: RangeInclusive<usize> = 2..=32;
+    /// Width of buses, which is as many bits as a number holds
+    pub const BUS_BITS: RangeInclusive<usize> = 1..=64;
+    /// Width of the select bus of multiplexers, demultiplexers, decoders and encoders
+    pub const SELECT_BITS: RangeInclusive<usize> = 1..=5;
+    /// Width of the numbers arithmetic components work on
+    pub const ARITHMETIC_BITS: RangeInclusive<usize> = 1..=32;
+    /// Width of the address bus of memories
+    pub const ADDRESS_BITS: RangeInclusive<usize> = 1..=16;
+    /// Width of the words stored in memories
+    pub const WORD_BITS: RangeInclusive<usize> = 1..=32;
+
+    pub fn input_len(&self) -> usize {
         match self {
             Component::Input(_) => 0,
             Component::Output(_) => 1,
-
-            Component::OrGate => 2,
-            Component::AndGate => 2,
-            Component::XorGate => 2,
+            Component::PushButton(_)
+            | Component::ToggleSwitch(_)
+            | Component::DipSwitch(_)
+            | Component::Constant(_) => 0,
+
+            Component::OrGate(inputs)
+            | Component::AndGate(inputs)
+            | Component::XorGate(inputs)
+            | Component::NandGate(inputs)
+            | Component::NorGate(inputs)
+            | Component::XnorGate(inputs) => *inputs,
             Component::NotGate => 1,
-            Component::Custom(_) => 0,
+            Component::BufferGate => 1,
+            Component::Splitter(_) => 1,
+            Component::Merger(width) => *width,
+            Component::TriState(_) => 2,
+            Component::Clock(_) => 0,
+            Component::DFlipFlop(_) => 2,
+            Component::JkFlipFlop(_) => 3,
+            Component::TFlipFlop(_) => 2,
+            Component::SrLatch(_) => 2,
+            Component::DLatch(_) => 2,
+            Component::Register(_) => 4,
+            Component::Multiplexer { select, .. } => (1 << select) + 1,
+            Component::Demultiplexer { .. } => 2,
+            Component::Decoder(_) => 1,
+            Component::PriorityEncoder(select) => 1 << select,
+            Component::Adder(_) => 3,
+            Component::Subtractor(_) => 3,
+            Component::Comparator(_) => 2,
+            Component::Multiplier(_) => 2,
+            Component::Rom(_) => 1,
+            Component::Ram(_) => 4,
+            Component::Led(_) => 1,
+            Component::SevenSegment(_) => 7,
+            Component::HexDisplay(_) => 1,
+            Component::Custom(subcircuit) => subcircuit.inputs.len(),
+            Component::Removed => 0,
         }
     }
 
-    pub const fn output_len(&self) -> usize {
+    pub fn output_len(&self) -> usize {
         match self {
             Component::Input(_) => 1,
             Component::Output(_) => 0,
-
-            Component::OrGate => 1,
-            Component::AndGate => 1,
-            Component::XorGate => 1,
+            Component::PushButton(_)
+            | Component::ToggleSwitch(_)
+            | Component::DipSwitch(_)
+            | Component::Constant(_) => 1,
+
+            Component::OrGate(_) => 1,
+            Component::AndGate(_) => 1,
+            Component::XorGate(_) => 1,
+            Component::NandGate(_) => 1,
+            Component::NorGate(_) => 1,
+            Component::XnorGate(_) => 1,
             Component::NotGate => 1,
-            Component::Custom(i) => *i,
+            Component::BufferGate => 1,
+            Component::Splitter(width) => *width,
+            Component::Merger(_) => 1,
+            Component::TriState(_) => 1,
+            Component::Clock(_) => 1,
+            Component::DFlipFlop(_) => 2,
+            Component::JkFlipFlop(_) => 2,
+            Component::TFlipFlop(_) => 2,
+            Component::SrLatch(_) => 2,
+            Component::DLatch(_) => 2,
+            Component::Register(_) => 1,
+            Component::Multiplexer { .. } => 1,
+            Component::Demultiplexer { select, .. } => 1 << select,
+            Component::Decoder(select) => 1 << select,
+            Component::PriorityEncoder(_) => 2,
+            Component::Adder(_) => 2,
+            Component::Subtractor(_) => 2,
+            Component::Comparator(_) => 3,
+            Component::Multiplier(_) => 1,
+            Component::Rom(_) => 1,
+            Component::Ram(_) => 1,
+            Component::Led(_) => 0,
+            Component::SevenSegment(_) => 0,
+            Component::HexDisplay(_) => 0,
+            Component::Custom(subcircuit) => subcircuit.outputs.len(),
+            Component::Removed => 0,
         }
     }
 
-    fn set_pin(&mut self, pin: usize, value: State) {
+    /// Number of bits carried by a pin, numbered like `RegisteredPin` (inputs first, then outputs)
+    pub fn pin_width(&self, pin: usize) -> usize {
         match self {
-            Component::Output(b) => *b = value,
-            _ => panic!("Component doesn't have input!"),
+            Component::Input(value) => value.width(),
+            Component::Output(value) => value.width(),
+            Component::DipSwitch(value) => value.width(),
+
+            Component::Splitter(width) if pin == 0 => *width,
+            Component::Merger(width) if pin == *width => *width,
+            Component::TriState(width) if pin != 1 => *width,
+            Component::Register(storage) if pin == 0 || pin == 4 => storage.value.width(),
+            Component::Multiplexer { select, width } => {
+                if pin == 1 << select {
+                    *select
+                } else {
+                    *width
+                }
+            }
+            Component::Demultiplexer { select, width } => {
+                if pin == 1 {
+                    *select
+                } else {
+                    *width
+                }
+            }
+            Component::Decoder(select) if pin == 0 => *select,
+            Component::PriorityEncoder(select) if pin == 1 << select => *select,
+            // Carries and borrows are single bits
+            Component::Adder(width) | Component::Subtractor(width)
+                if pin == 0 || pin == 1 || pin == 3 =>
+            {
+                *width
+            }
+            Component::Comparator(width) if pin < 2 => *width,
+            Component::Multiplier(width) if pin < 2 => *width,
+            Component::Multiplier(width) => width * 2,
+            Component::Rom(memory) | Component::Ram(memory) if pin == 0 => memory.address,
+            Component::Rom(memory) => memory.width,
+            // Write enable and clock are single bits
+            Component::Ram(memory) if pin == 1 || pin == 4 => memory.width,
+            Component::HexDisplay(_) => 4,
+            Component::Custom(subcircuit) => subcircuit.port_width(pin),
+            _ => 1,
         }
     }
 
-    fn get_pin(&mut self, pin: usize) -> State {
+    /// Whether the component only changes state on a rising clock edge
+    pub fn is_edge_triggered(&self) -> bool {
+        matches!(
+            self,
+            Component::DFlipFlop(_)
+                | Component::JkFlipFlop(_)
+                | Component::TFlipFlop(_)
+                | Component::Register(_)
+                | Component::Ram(_)
+        )
+    }
+
+    /// Whether the outputs can go high impedance, which is what lets several of them drive the same net
+    pub fn can_float(&self) -> bool {
+        matches!(self, Component::TriState(_))
+    }
+
+    /// Time it takes for a change on an input to show up on the outputs, unless overridden with `Simulator::set_delay`
+    pub const fn default_delay(&self) -> SimTime {
         match self {
-            Component::Input(b) => *b,
-            _ => panic!("Component doesn't have output!"),
+            Component::Input(_) => 0,
+            Component::Output(_) => 0,
+            Component::PushButton(_) => 0,
+            Component::ToggleSwitch(_) => 0,
+            Component::DipSwitch(_) => 0,
+            Component::Constant(_) => 0,
+
+            Component::OrGate(_) => 1,
+            Component::AndGate(_) => 1,
+            Component::XorGate(_) => 1,
+            Component::NandGate(_) => 1,
+            Component::NorGate(_) => 1,
+            Component::XnorGate(_) => 1,
+            Component::NotGate => 1,
+            Component::BufferGate => 1,
+            // Splitters and mergers are just wiring
+            Component::Splitter(_) => 0,
+            Component::Merger(_) => 0,
+            Component::TriState(_) => 1,
+            Component::Clock(_) => 0,
+            Component::DFlipFlop(_) => 1,
+            Component::JkFlipFlop(_) => 1,
+            Component::TFlipFlop(_) => 1,
+            Component::SrLatch(_) => 1,
+            Component::DLatch(_) => 1,
+            Component::Register(_) => 1,
+            Component::Multiplexer { .. } => 1,
+            Component::Demultiplexer { .. } => 1,
+            Component::Decoder(_) => 1,
+            Component::PriorityEncoder(_) => 1,
+            Component::Adder(_) => 1,
+            Component::Subtractor(_) => 1,
+            Component::Comparator(_) => 1,
+            Component::Multiplier(_) => 1,
+            Component::Rom(_) => 1,
+            Component::Ram(_) => 1,
+            // Displays only show what they get, like outputs
+            Component::Led(_) => 0,
+            Component::SevenSegment(_) => 0,
+            Component::HexDisplay(_) => 0,
+            // Subcircuits settle internally, so the whole block switches at once
+            Component::Custom(_) => 0,
+            Component::Removed => 0,
         }
     }
 
-    fn set_input(&mut self, pin: usize, value: State) {
+    fn set_input(&mut self, _pin: usize, value: StateVec) {
         match self {
             Component::Input(inp) => *inp = value,
             _ => panic!("Component isn't an input!"),
         }
     }
 
-    fn inspect_pin(&mut self, pin: usize) -> State {
+    fn inspect_pin(&mut self, _pin: usize) -> StateVec {
         match self {
-            Component::Input(b) => *b,
-            Component::Output(b) => *b,
+            Component::Input(b) => b.clone(),
+            Component::Output(b) => b.clone(),
             _ => panic!("component can't be inspected"),
         }
     }
@@ -245,450 +763,3074 @@ impl Component {
         match self {
             Component::Input(_) => "input",
             Component::Output(_) => "output",
-            Component::OrGate => "or gate",
-            Component::AndGate => "and gate",
-            Component::XorGate => "xor gate",
+            Component::PushButton(_) => "push button",
+            Component::ToggleSwitch(_) => "toggle switch",
+            Component::DipSwitch(_) => "dip switch",
+            Component::Constant(_) => "constant",
+            Component::OrGate(_) => "or gate",
+            Component::AndGate(_) => "and gate",
+            Component::XorGate(_) => "xor gate",
+            Component::NandGate(_) => "nand gate",
+            Component::NorGate(_) => "nor gate",
+            Component::XnorGate(_) => "xnor gate",
             Component::NotGate => "not gate",
-            Component::Custom(_) => "custom",
+            Component::BufferGate => "buffer",
+            Component::Splitter(_) => "splitter",
+            Component::Merger(_) => "merger",
+            Component::TriState(_) => "tri-state buffer",
+            Component::Clock(_) => "clock",
+            Component::DFlipFlop(_) => "d flip-flop",
+            Component::JkFlipFlop(_) => "jk flip-flop",
+            Component::TFlipFlop(_) => "t flip-flop",
+            Component::SrLatch(_) => "sr latch",
+            Component::DLatch(_) => "d latch",
+            Component::Register(_) => "register",
+            Component::Multiplexer { .. } => "multiplexer",
+            Component::Demultiplexer { .. } => "demultiplexer",
+            Component::Decoder(_) => "decoder",
+            Component::PriorityEncoder(_) => "priority encoder",
+            Component::Adder(_) => "adder",
+            Component::Subtractor(_) => "subtractor",
+            Component::Comparator(_) => "comparator",
+            Component::Multiplier(_) => "multiplier",
+            Component::Rom(_) => "rom",
+            Component::Ram(_) => "ram",
+            Component::Led(_) => "led",
+            Component::SevenSegment(_) => "7-segment display",
+            Component::HexDisplay(_) => "hex display",
+            Component::Custom(subcircuit) => &subcircuit.name,
+            Component::Removed => "removed",
         }
     }
 
-    fn sim_pin(&mut self, pin: &RegisteredPin, params: &mut SimParams) -> State {
-        params.pin_cache.get(pin).copied().unwrap_or_else(|| {
-            let component = &params.sim.components[pin.0];
-            let mut component = component.borrow_mut();
-
-            let right_result = component.rev_sim(SimParams {
-                input: *pin,
-                output: RegisteredPin(params.input.0, 1),
-                pin_cache: params.pin_cache,
-                sim: params.sim,
-            });
-
-            right_result
-        })
-    }
-
-    fn binary_sim(
-        &mut self,
-        left: &RegisteredPin,
-        right: &RegisteredPin,
-        params: &mut SimParams,
-    ) -> Option<(State, State)> {
-        let Some(left) = params.sim.out_to_in.get(&left) else {
-            eprintln!(
-                "WARNING: No connection for 'and gate' component, index: {:?}",
-                left
-            );
-            return None;
-        };
-
-        let Some(right) = params.sim.out_to_in.get(&right) else {
-            eprintln!(
-                "WARNING: No connection for 'and gate' component, index: {:?}",
-                right
-            );
-            return None;
-        };
-
-        let left = self.sim_pin(left, params);
-        let right = self.sim_pin(right, params);
-
-        Some((left, right))
-    }
-
-    #[inline]
-    fn nsim<const N: usize>(
-        &mut self,
-        pins: [&RegisteredPin; N],
-        params: &mut SimParams,
-    ) -> Option<[State; N]> {
-        let result = pins
-            .into_iter()
-            .map(|pin| {
-                let Some(pin) = params.sim.out_to_in.get(pin) else {
-                    eprintln!(
-                        "WARNING: No connection for 'and gate' component, index: {:?}",
-                        pin
-                    );
-                    return Invalid;
-                };
-
-                self.sim_pin(pin, params)
-            })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
-
-        Some(result)
-    }
-
-    fn rev_sim(&mut self, mut params: SimParams) -> State {
+    /// Computes the values of the output pins from the values on the input pins. `inputs` has `input_len` entries,
+    /// each as wide as its pin.
+    fn evaluate(&mut self, inputs: &[StateVec]) -> Vec<StateVec> {
         match self {
-            Component::Input(i) => {
-                params.pin_cache.insert(params.output, *i);
-
-                *i
-            }
+            Component::Input(i) => vec![i.clone()],
+            Component::PushButton(value)
+            | Component::ToggleSwitch(value)
+            | Component::Constant(value) => vec![(*value).into()],
+            Component::DipSwitch(value) => vec![value.clone()],
             Component::Output(o) => {
-                assert_eq!(params.input.1, 0);
+                *o = inputs[0].clone();
 
-                let Some(next) = params.sim.out_to_in.get(&params.input) else {
-                    eprintln!(
-                        "WARNING: No connection for 'output' component, index: {:?}",
-                        params.input
-                    );
-                    return State::Invalid;
-                };
-
-                if let Some(state) = params.pin_cache.get(next) {
-                    return *state;
+                vec![]
+            }
+            Component::OrGate(_) => vec![any_high(inputs).into()],
+            Component::AndGate(_) => vec![all_high(inputs).into()],
+            Component::XorGate(_) => vec![odd_parity(inputs).into()],
+            Component::NandGate(_) => vec![invert(all_high(inputs)).into()],
+            Component::NorGate(_) => vec![invert(any_high(inputs)).into()],
+            Component::XnorGate(_) => vec![invert(odd_parity(inputs)).into()],
+            Component::NotGate => vec![invert(inputs[0].bit(0)).into()],
+            Component::BufferGate => vec![match inputs[0].bit(0) {
+                HighZ => Invalid,
+                bit => bit,
+            }
+            .into()],
+            Component::Splitter(_) => inputs[0].bits().iter().map(|bit| (*bit).into()).collect(),
+            Component::Merger(_) => vec![inputs.iter().map(|input| input.bit(0)).collect()],
+            Component::Clock(clock) => vec![clock.value.into()],
+            Component::DFlipFlop(storage) => {
+                if storage.rising_edge(inputs[1].bit(0)) {
+                    storage.value = inputs[0].clone();
                 }
 
-                let component = &params.sim.components[next.0];
-                let mut component = component.borrow_mut();
-
-                let result = component.rev_sim(SimParams {
-                    input: *next,
-                    output: params.input,
-                    pin_cache: params.pin_cache,
-                    sim: params.sim,
-                });
-                *o = result;
-
-                result
-            }
-            Component::OrGate => {
-                let Some(result) = self.nsim(
-                    [
-                        &RegisteredPin(params.input.0, 0),
-                        &RegisteredPin(params.input.0, 1),
-                    ],
-                    &mut params,
-                ) else {
-                    return Invalid;
-                };
-
-                match result {
-                    [Invalid, _] | [_, Invalid] => Invalid,
-                    [High, _] | [_, High] => High,
-                    _ => Low,
+                storage.outputs()
+            }
+            Component::JkFlipFlop(storage) => {
+                if storage.rising_edge(inputs[2].bit(0)) {
+                    let q = storage.value.bit(0);
+                    storage.value = match [inputs[0].bit(0), inputs[1].bit(0)] {
+                        [Low, Low] => q,
+                        [High, Low] => High,
+                        [Low, High] => Low,
+                        [High, High] => invert(q),
+                        _ => Invalid,
+                    }
+                    .into();
                 }
+
+                storage.outputs()
             }
-            Component::AndGate => {
-                let Some(result) = self.nsim(
-                    [
-                        &RegisteredPin(params.input.0, 0),
-                        &RegisteredPin(params.input.0, 1),
-                    ],
-                    &mut params,
-                ) else {
-                    return Invalid;
-                };
+            Component::TFlipFlop(storage) => {
+                if storage.rising_edge(inputs[1].bit(0)) {
+                    let q = storage.value.bit(0);
+                    storage.value = match inputs[0].bit(0) {
+                        Low => q,
+                        High => invert(q),
+                        Invalid | HighZ => Invalid,
+                    }
+                    .into();
+                }
 
-                match result {
-                    [Invalid, _] | [_, Invalid] => Invalid,
-                    [High, High] => High,
-                    _ => Low,
+                storage.outputs()
+            }
+            Component::SrLatch(storage) => {
+                let q = storage.value.bit(0);
+                storage.value = match [inputs[0].bit(0), inputs[1].bit(0)] {
+                    [Low, Low] => q,
+                    [High, Low] => High,
+                    [Low, High] => Low,
+                    // Setting and resetting at once leaves the latch in an undefined state
+                    _ => Invalid,
                 }
+                .into();
+
+                storage.outputs()
             }
-            Component::XorGate => {
-                let Some(result) = self.nsim(
-                    [
-                        &RegisteredPin(params.input.0, 0),
-                        &RegisteredPin(params.input.0, 1),
-                    ],
-                    &mut params,
-                ) else {
-                    return Invalid;
-                };
+            Component::DLatch(storage) => {
+                match inputs[1].bit(0) {
+                    High => storage.value = inputs[0].clone(),
+                    Low => (),
+                    // Not knowing whether the latch is open only matters if D differs from what it holds
+                    Invalid | HighZ => {
+                        if storage.value != inputs[0] {
+                            storage.value = Invalid.into();
+                        }
+                    }
+                }
 
-                match result {
-                    [Invalid, _] | [_, Invalid] => Invalid,
-                    [High, High] => Low,
-                    [High, _] | [_, High] => High,
-                    _ => Low,
+                storage.outputs()
+            }
+            Component::Register(storage) => {
+                let width = storage.value.width();
+                let edge = storage.rising_edge(inputs[1].bit(0));
+
+                match inputs[3].bit(0) {
+                    High => storage.value = StateVec::new(width, Low),
+                    Low => {
+                        if edge {
+                            match inputs[2].bit(0) {
+                                High => storage.value = inputs[0].clone(),
+                                Low => (),
+                                Invalid | HighZ => storage.value = StateVec::new(width, Invalid),
+                            }
+                        }
+                    }
+                    Invalid | HighZ => storage.value = StateVec::new(width, Invalid),
                 }
+
+                vec![storage.value.clone()]
             }
-            Component::NotGate => {
-                let Some(result) = self.nsim([&RegisteredPin(params.input.0, 0)], &mut params)
-                else {
-                    return Invalid;
-                };
+            Component::Multiplexer { select, width } => {
+                vec![match inputs[1 << *select].to_number() {
+                    Some(index) => inputs[index as usize].clone(),
+                    None => StateVec::new(*width, Invalid),
+                }]
+            }
+            Component::Demultiplexer { select, width } => {
+                let index = inputs[1].to_number();
+
+                (0..1 << *select)
+                    .map(|output| match index {
+                        Some(index) if index == output => inputs[0].clone(),
+                        Some(_) => StateVec::new(*width, Low),
+                        None => StateVec::new(*width, Invalid),
+                    })
+                    .collect()
+            }
+            Component::Decoder(select) => {
+                let index = inputs[0].to_number();
+
+                (0..1 << *select)
+                    .map(|output| {
+                        match index {
+                            Some(index) if index == output => High,
+                            Some(_) => Low,
+                            None => Invalid,
+                        }
+                        .into()
+                    })
+                    .collect()
+            }
+            Component::PriorityEncoder(select) => {
+                // Scan from the highest priority input down. An invalid input above the first high one could be
+                // hiding a higher index.
+                let mut outputs = vec![StateVec::new(*select, Low), Low.into()];
+                for (index, input) in inputs.iter().enumerate().rev() {
+                    match input.bit(0) {
+                        High => {
+                            outputs =
+                                vec![StateVec::from_number(index as u64, *select), High.into()];
+                            break;
+                        }
+                        Low => (),
+                        Invalid | HighZ => {
+                            outputs = vec![StateVec::new(*select, Invalid), Invalid.into()];
+                            break;
+                        }
+                    }
+                }
 
-                match result {
-                    [Invalid] => Invalid,
-                    [High] => Low,
-                    [Low] => High,
+                outputs
+            }
+            // Any invalid or floating input bit makes every output invalid
+            Component::Adder(width) => match numbers(inputs) {
+                Some([a, b, carry]) => {
+                    let sum = a + b + carry;
+                    vec![
+                        StateVec::from_number(sum, *width),
+                        StateVec::from_number(sum >> *width, 1),
+                    ]
+                }
+                _ => vec![StateVec::new(*width, Invalid), Invalid.into()],
+            },
+            Component::Subtractor(width) => match numbers(inputs) {
+                Some([a, b, borrow]) => {
+                    let difference = a.wrapping_sub(b).wrapping_sub(borrow);
+                    vec![
+                        StateVec::from_number(difference, *width),
+                        (if a < b + borrow { High } else { Low }).into(),
+                    ]
                 }
+                _ => vec![StateVec::new(*width, Invalid), Invalid.into()],
+            },
+            Component::Comparator(_) => match numbers(inputs) {
+                Some([a, b]) => [a < b, a == b, a > b]
+                    .into_iter()
+                    .map(|result| (if result { High } else { Low }).into())
+                    .collect(),
+                None => vec![Invalid.into(); 3],
+            },
+            Component::Multiplier(width) => match numbers(inputs) {
+                Some([a, b]) => vec![StateVec::from_number(a * b, *width * 2)],
+                None => vec![StateVec::new(*width * 2, Invalid)],
+            },
+            Component::Rom(memory) => vec![memory.read(&inputs[0])],
+            Component::Ram(memory) => {
+                memory.write(&inputs[0], &inputs[1], inputs[2].bit(0), inputs[3].bit(0));
+                vec![memory.read(&inputs[0])]
             }
-            Component::Custom(_) => Low,
+            Component::Led(value) => {
+                *value = inputs[0].bit(0);
+                vec![]
+            }
+            Component::SevenSegment(value) => {
+                *value = inputs.iter().map(|input| input.bit(0)).collect();
+                vec![]
+            }
+            Component::HexDisplay(value) => {
+                *value = inputs[0].clone();
+                vec![]
+            }
+            Component::TriState(width) => vec![match inputs[1].bit(0) {
+                High => inputs[0].clone(),
+                Low => StateVec::new(*width, HighZ),
+                Invalid | HighZ => StateVec::new(*width, Invalid),
+            }],
+            Component::Custom(subcircuit) => subcircuit.evaluate(inputs),
+            Component::Removed => vec![],
         }
     }
 }
 
-/// Represents a registered pin in the simulator. First element is componenet index, second is pin index in component.
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct RegisteredPin(usize, usize);
-
-/// Registered one connection in the simulator
-#[derive(Debug, Hash, PartialEq, Eq)]
-struct Connection(RegisteredPin, RegisteredPin);
-
-pub struct Simulator {
-    /// Indicies into `components` field of input components
-    input_components: Vec<usize>,
-    /// Indicies into `components` field of output components
-    output_components: Vec<usize>,
-
-    /// Contains all of the components that the simlulator contains
-    components: Vec<Rc<RefCell<Component>>>,
-
-    /// Contains all of the connection mappings with inputs to outputs
-    in_to_out: HashMap<RegisteredPin, RegisteredPin>,
-    /// Contains all of the connection mappings with output to inputs
-    out_to_in: HashMap<RegisteredPin, RegisteredPin>,
+/// A circuit packaged up as a single component. Named ports map to `Input` and `Output` components
+/// inside the netlist. Every instance owns its own copy of the netlist so stateful parts don't interfere.
+#[derive(Clone)]
+pub struct Subcircuit {
+    name: String,
+    sim: Simulator,
+
+    /// Port names and the index of the `Input` component they drive, in pin order
+    inputs: Vec<(String, usize)>,
+    /// Port names and the index of the `Output` component they read, in pin order
+    outputs: Vec<(String, usize)>,
 }
 
-impl Simulator {
-    fn new() -> Simulator {
-        Simulator {
-            input_components: Vec::new(),
-            output_components: Vec::new(),
-            components: Vec::new(),
-            in_to_out: HashMap::new(),
-            out_to_in: HashMap::new(),
+impl Subcircuit {
+    pub fn new(name: impl Into<String>, sim: Simulator) -> Subcircuit {
+        Subcircuit {
+            name: name.into(),
+            sim,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
         }
     }
 
-    fn get_component(&self, handle: &ComponentHandle) -> &Rc<RefCell<Component>> {
-        &self.components[handle.0]
+    /// Exposes an `Input` component of the netlist as the next input pin
+    pub fn with_input(mut self, name: impl Into<String>, component: usize) -> Self {
+        debug_assert!(self.sim.input_components.contains(&component));
+        self.inputs.push((name.into(), component));
+        self
     }
 
-    fn insert_component(&mut self, component: Component) -> usize {
-        let index = self.components.len();
-        match component {
-            Component::Input(_) => self.input_components.push(index),
-            Component::Output(_) => self.output_components.push(index),
-            _ => (),
-        }
+    /// Exposes an `Output` component of the netlist as the next output pin
+    pub fn with_output(mut self, name: impl Into<String>, component: usize) -> Self {
+        debug_assert!(self.sim.output_components.contains(&component));
+        self.outputs.push((name.into(), component));
+        self
+    }
 
-        self.components.push(Rc::new(RefCell::new(component)));
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-        index
+    /// Name of a pin, using the same numbering as `RegisteredPin` (inputs first, then outputs)
+    pub fn port_name(&self, pin: usize) -> Option<&str> {
+        self.inputs
+            .iter()
+            .chain(self.outputs.iter())
+            .nth(pin)
+            .map(|(name, _)| name.as_str())
     }
 
-    fn connect(&mut self, input: RegisteredPin, output: RegisteredPin) {
-        self.in_to_out.insert(input.clone(), output.clone());
-        self.out_to_in.insert(output, input);
+    /// Width of a pin, using the same numbering as `port_name`
+    pub fn port_width(&self, pin: usize) -> usize {
+        self.inputs
+            .iter()
+            .chain(self.outputs.iter())
+            .nth(pin)
+            .map_or(1, |(_, component)| {
+                self.sim.pin_width(RegisteredPin(*component, 0))
+            })
     }
 
-    fn set_pin(&mut self, pin: RegisteredPin, value: State) {
-        let component = &self.components[pin.0];
-        let mut component = component.borrow_mut();
+    fn evaluate(&mut self, inputs: &[StateVec]) -> Vec<StateVec> {
+        for ((_, component), value) in self.inputs.iter().zip(inputs) {
+            self.sim
+                .set_input(RegisteredPin(*component, 0), value.clone());
+        }
+
+        if let Err(err) = self.sim.settle() {
+            eprintln!("WARNING: in subcircuit '{}': {err}", self.name);
+        }
 
-        component.set_pin(pin.1, value);
+        self.outputs
+            .iter()
+            .map(|(_, component)| self.sim.inspect_pin(RegisteredPin(*component, 0)))
+            .collect()
     }
+}
 
-    fn get_pin(&self, pin: RegisteredPin) -> State {
-        let component = &self.components[pin.0];
-        let mut component = component.borrow_mut();
+/// Stores the new clock level in `last` and returns whether it went from low to high
+fn rising_edge(last: &mut State, clock: State) -> bool {
+    let edge = *last == Low && clock == High;
+    *last = clock;
+    edge
+}
+
+/// Value held by a flip-flop, latch or register between evaluations
+#[derive(Debug, Clone, PartialEq)]
+pub struct Storage {
+    value: StateVec,
+    /// Clock level at the last evaluation, used to find rising edges
+    clock: State,
+}
 
-        component.get_pin(pin.1)
+impl Storage {
+    /// Starts out low. The previous clock level is unknown, so a clock that's already high doesn't count as an edge.
+    pub fn new(width: usize) -> Storage {
+        Storage {
+            value: StateVec::new(width, Low),
+            clock: Invalid,
+        }
     }
 
-    fn set_input(&mut self, pin: RegisteredPin, value: State) {
-        let component = &self.components[pin.0];
-        let mut component = component.borrow_mut();
+    /// Remembers the clock level and returns whether it just went from low to high
+    fn rising_edge(&mut self, clock: State) -> bool {
+        rising_edge(&mut self.clock, clock)
+    }
 
-        component.set_input(pin.1, value);
+    /// Q and not Q of a single bit value
+    fn outputs(&self) -> Vec<StateVec> {
+        let q = self.value.bit(0);
+        vec![q.into(), invert(q).into()]
     }
+}
 
-    fn inspect_pin(&self, pin: RegisteredPin) -> State {
-        let component = &self.components[pin.0];
-        let mut component = component.borrow_mut();
+/// Square wave source. The output rises at `phase` and then every `period` time units, and stays high for
+/// `duty_cycle` of each period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    pub period: SimTime,
+    pub phase: SimTime,
+    pub duty_cycle: f64,
+    /// Level at the last time `Simulator::update_clocks` ran
+    value: State,
+}
 
-        component.inspect_pin(pin.1)
+impl Clock {
+    pub fn new(period: SimTime, phase: SimTime, duty_cycle: f64) -> Clock {
+        let mut clock = Clock {
+            period,
+            phase,
+            duty_cycle,
+            value: Low,
+        };
+        clock.value = clock.level_at(0);
+        clock
     }
 
-    fn tick(&mut self) {
-        let mut cache = HashMap::new();
+    /// How long the output stays high in each period
+    fn high_time(&self) -> SimTime {
+        (self.period as f64 * self.duty_cycle.clamp(0.0, 1.0)).round() as SimTime
+    }
 
-        for output in &self.output_components {
-            let component = &self.components[*output];
-            let mut component = component.borrow_mut();
+    /// Time since the last rising edge
+    fn offset(&self, time: SimTime) -> SimTime {
+        (time % self.period + self.period - self.phase % self.period) % self.period
+    }
 
-            component.rev_sim(SimParams {
-                input: RegisteredPin(*output, 0),
-                output: RegisteredPin(0, 0),
-                sim: self,
-                pin_cache: &mut cache,
-            });
+    fn level_at(&self, time: SimTime) -> State {
+        if self.period == 0 || self.offset(time) >= self.high_time() {
+            Low
+        } else {
+            High
         }
     }
-}
 
-#[cfg(test)]
+    /// First time after `time` where the output rises or falls. Clocks with a period of zero never change.
+    fn next_edge(&self, time: SimTime) -> Option<SimTime> {
+        if self.period == 0 {
+            return None;
+        }
+
+        let offset = self.offset(time);
+        let high_time = self.high_time();
+
+        Some(if offset < high_time {
+            time + high_time - offset
+        } else {
+            time + self.period - offset
+        })
+    }
+}
+
+/// Represents a registered pin in the simulator. First element is componenet index, second is pin index in component.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RegisteredPin(usize, usize);
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NetId(usize);
+
+/// Pins joined by wires. Every sink sees the values of all drivers combined with `resolve`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Net {
+    drivers: Vec<RegisteredPin>,
+    sinks: Vec<RegisteredPin>,
+    /// Wires making up the net, as `(driver, sink)`
+    wires: Vec<(RegisteredPin, RegisteredPin)>,
+}
+
+impl Net {
+    pub fn drivers(&self) -> &[RegisteredPin] {
+        &self.drivers
+    }
+
+    pub fn sinks(&self) -> &[RegisteredPin] {
+        &self.sinks
+    }
+
+    pub fn wires(&self) -> &[(RegisteredPin, RegisteredPin)] {
+        &self.wires
+    }
+}
+
+/// Or of the first bit of every input. A high input decides the output even if others are invalid or floating,
+/// which lets feedback loops recover from an invalid state.
+fn any_high(inputs: &[StateVec]) -> State {
+    let bits = || inputs.iter().map(|input| input.bit(0));
+
+    if bits().any(|bit| bit == High) {
+        High
+    } else if bits().any(|bit| matches!(bit, Invalid | HighZ)) {
+        Invalid
+    } else {
+        Low
+    }
+}
+
+/// And of the first bit of every input. A low input decides the output even if others are invalid or floating.
+fn all_high(inputs: &[StateVec]) -> State {
+    let bits = || inputs.iter().map(|input| input.bit(0));
+
+    if bits().any(|bit| bit == Low) {
+        Low
+    } else if bits().any(|bit| matches!(bit, Invalid | HighZ)) {
+        Invalid
+    } else {
+        High
+    }
+}
+
+/// Whether an odd number of inputs are high. Xor has no controlling value, so any invalid input spoils it.
+fn odd_parity(inputs: &[StateVec]) -> State {
+    let mut parity = Low;
+    for input in inputs {
+        parity = match (parity, input.bit(0)) {
+            (_, Invalid | HighZ) => return Invalid,
+            (parity, Low) => parity,
+            (parity, _) => invert(parity),
+        };
+    }
+
+    parity
+}
+
+/// Inputs of an arithmetic component as numbers, or `None` if any of them has a bit that isn't high or low
+fn numbers<const N: usize>(inputs: &[StateVec]) -> Option<[u64; N]> {
+    let mut numbers = [0; N];
+    for (number, input) in numbers.iter_mut().zip(inputs) {
+        *number = input.to_number()?;
+    }
+
+    Some(numbers)
+}
+
+/// Logical not. Floating values count as invalid.
+fn invert(state: State) -> State {
+    match state {
+        Invalid | HighZ => Invalid,
+        High => Low,
+        Low => High,
+    }
+}
+
+/// Value of a wire driven by two outputs. High impedance gives way to the other value, and outputs that disagree
+/// give an invalid value.
+fn resolve(a: State, b: State) -> State {
+    match (a, b) {
+        (HighZ, other) | (other, HighZ) => other,
+        (a, b) if a == b => a,
+        _ => Invalid,
+    }
+}
+
+pub struct Simulator {
+    /// Indicies into `components` field of input components
+    input_components: Vec<usize>,
+    /// Indicies into `components` field of output components
+    output_components: Vec<usize>,
+
+    /// Contains all of the components that the simlulator contains
+    components: Vec<Rc<RefCell<Component>>>,
+
+    /// Every net, ordered by id so iteration is deterministic
+    nets: BTreeMap<NetId, Net>,
+    /// Net each connected pin belongs to
+    pin_nets: HashMap<RegisteredPin, NetId>,
+    /// Id given to the next new net
+    next_net: usize,
+
+    /// Current simulation time
+    time: SimTime,
+    /// Pending pin value changes, bucketed by the time they take effect
+    events: BTreeMap<SimTime, Vec<(RegisteredPin, StateVec)>>,
+    /// Components whose inputs changed and need to be evaluated at the current time
+    dirty: BTreeSet<usize>,
+    /// Value currently driven onto each output pin
+    pin_states: HashMap<RegisteredPin, StateVec>,
+    /// Per component overrides of `Component::default_delay`
+    delays: HashMap<usize, SimTime>,
+}
+
+impl Clone for Simulator {
+    /// Deep copies the netlist, so the clone doesn't share component state with the original
+    fn clone(&self) -> Self {
+        Simulator {
+            input_components: self.input_components.clone(),
+            output_components: self.output_components.clone(),
+            components: self
+                .components
+                .iter()
+                .map(|component| Rc::new(RefCell::new(component.borrow().clone())))
+                .collect(),
+            nets: self.nets.clone(),
+            pin_nets: self.pin_nets.clone(),
+            next_net: self.next_net,
+
+            time: self.time,
+            events: self.events.clone(),
+            dirty: self.dirty.clone(),
+            pin_states: self.pin_states.clone(),
+            delays: self.delays.clone(),
+        }
+    }
+}
+
+impl Simulator {
+    /// Number of steps `run_until_quiescent` and `run_until` take before giving up
+    const STEP_LIMIT: usize = 10_000;
+    /// Number of passes `settle` makes over a feedback loop before declaring it oscillating
+    const ITERATION_LIMIT: usize = 100;
+
+    fn new() -> Simulator {
+        Simulator {
+            input_components: Vec::new(),
+            output_components: Vec::new(),
+            components: Vec::new(),
+            nets: BTreeMap::new(),
+            pin_nets: HashMap::new(),
+            next_net: 0,
+
+            time: 0,
+            events: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            pin_states: HashMap::new(),
+            delays: HashMap::new(),
+        }
+    }
+
+    fn get_component(&self, handle: &ComponentHandle) -> &Rc<RefCell<Component>> {
+        &self.components[handle.0]
+    }
+
+    fn insert_component(&mut self, component: Component) -> usize {
+        let index = self.components.len();
+        match component {
+            Component::Input(_) => self.input_components.push(index),
+            Component::Output(_) => self.output_components.push(index),
+            _ => (),
+        }
+
+        self.components.push(Rc::new(RefCell::new(component)));
+        self.dirty.insert(index);
+
+        index
+    }
+
+    /// Takes a component out of the circuit along with all of its wires. Its slot is kept as a tombstone,
+    /// so other handles stay valid and `restore_component` can put it back.
+    fn remove_component(&mut self, handle: ComponentHandle) -> Component {
+        let index = handle.0;
+
+        for (driver, sink) in self.connections_of(handle) {
+            self.disconnect(driver, sink);
+        }
+
+        self.input_components.retain(|i| *i != index);
+        self.output_components.retain(|i| *i != index);
+        self.pin_states.retain(|pin, _| pin.0 != index);
+        self.events
+            .values_mut()
+            .for_each(|changes| changes.retain(|(pin, _)| pin.0 != index));
+        self.dirty.remove(&index);
+
+        self.components[index].replace(Component::Removed)
+    }
+
+    /// Puts a component back into the slot it was removed from. Its wires have to be reconnected separately.
+    fn restore_component(&mut self, handle: ComponentHandle, component: Component) {
+        let index = handle.0;
+        debug_assert!(matches!(
+            *self.components[index].borrow(),
+            Component::Removed
+        ));
+
+        match component {
+            Component::Input(_) => self.input_components.push(index),
+            Component::Output(_) => self.output_components.push(index),
+            _ => (),
+        }
+
+        self.components[index].replace(component);
+        self.dirty.insert(index);
+    }
+
+    /// Copies some components into a new simulator, along with the wires between them and their delays.
+    /// Also returns the mapping from indices in this simulator to indices in the new one.
+    fn extract(&self, handles: &[ComponentHandle]) -> (Simulator, HashMap<usize, usize>) {
+        let mut sim = Simulator::new();
+        let mut ids = HashMap::new();
+
+        for handle in handles {
+            let index = sim.insert_component(self.components[handle.0].borrow().clone());
+            ids.insert(handle.0, index);
+
+            if let Some(delay) = self.delays.get(&handle.0) {
+                sim.delays.insert(index, *delay);
+            }
+        }
+
+        for (driver, sink) in self.connections() {
+            if let (Some(from), Some(to)) = (ids.get(&driver.0), ids.get(&sink.0)) {
+                sim.connect(RegisteredPin(*from, driver.1), RegisteredPin(*to, sink.1));
+            }
+        }
+
+        (sim, ids)
+    }
+
+    /// Inserts copies of every component of `other` with fresh handles, wired up the same way.
+    /// Returns the mapping from indices in `other` to indices in this simulator.
+    fn merge(&mut self, other: &Simulator) -> HashMap<usize, usize> {
+        let mut ids = HashMap::new();
+
+        for (old, component) in other.components.iter().enumerate() {
+            let component = component.borrow();
+            if matches!(*component, Component::Removed) {
+                continue;
+            }
+
+            let index = self.insert_component(component.clone());
+            ids.insert(old, index);
+
+            if let Some(delay) = other.delays.get(&old) {
+                self.delays.insert(index, *delay);
+            }
+        }
+
+        for (driver, sink) in other.connections() {
+            self.connect(
+                RegisteredPin(ids[&driver.0], driver.1),
+                RegisteredPin(ids[&sink.0], sink.1),
+            );
+        }
+
+        ids
+    }
+
+    /// Every wire as a `(driver, sink)` pair
+    fn connections(&self) -> impl Iterator<Item = (RegisteredPin, RegisteredPin)> + '_ {
+        self.nets.values().flat_map(|net| net.wires.iter().copied())
+    }
+
+    /// Every output pin paired with every input pin it affects. Unlike `connections` this includes pins that
+    /// share a net without a wire between them.
+    fn links(&self) -> impl Iterator<Item = (RegisteredPin, RegisteredPin)> + '_ {
+        self.nets.values().flat_map(|net| {
+            net.drivers
+                .iter()
+                .flat_map(|driver| net.sinks.iter().map(|sink| (*driver, *sink)))
+        })
+    }
+
+    /// Wires going into or out of a component, as `(driver, sink)` pairs
+    fn connections_of(&self, handle: ComponentHandle) -> Vec<(RegisteredPin, RegisteredPin)> {
+        self.connections()
+            .filter(|(driver, sink)| driver.0 == handle.0 || sink.0 == handle.0)
+            .collect()
+    }
+
+    fn nets(&self) -> impl Iterator<Item = &Net> {
+        self.nets.values()
+    }
+
+    /// Net a pin is connected to, if any
+    fn net_of(&self, pin: RegisteredPin) -> Option<&Net> {
+        self.pin_nets.get(&pin).map(|id| &self.nets[id])
+    }
+
+    /// Value on a net as seen by a pin of the given width. Drivers of a different width make it invalid.
+    fn net_value(&self, net: &Net, width: usize) -> StateVec {
+        net.drivers
+            .iter()
+            .fold(StateVec::new(width, HighZ), |value, driver| {
+                match self.pin_states.get(driver) {
+                    Some(driven) if driven.width() == width => value
+                        .bits()
+                        .iter()
+                        .zip(driven.bits())
+                        .map(|(a, b)| resolve(*a, *b))
+                        .collect(),
+                    _ => StateVec::new(width, Invalid),
+                }
+            })
+    }
+
+    /// Checks whether `driver` can drive `sink` with a new wire
+    fn check_connection(
+        &self,
+        driver: RegisteredPin,
+        sink: RegisteredPin,
+    ) -> Result<(), ConnectionError> {
+        {
+            let component = self.components[driver.0].borrow();
+            let inputs = component.input_len();
+            if driver.1 < inputs || driver.1 >= inputs + component.output_len() {
+                return Err(ConnectionError::NotAnOutput);
+            }
+        }
+
+        if sink.1 >= self.components[sink.0].borrow().input_len() {
+            return Err(ConnectionError::NotAnInput);
+        }
+
+        if self
+            .net_of(sink)
+            .is_some_and(|net| net.wires.contains(&(driver, sink)))
+        {
+            return Err(ConnectionError::AlreadyConnected);
+        }
+
+        // Drivers that can't float would fight over the net the wire creates
+        let drivers = [driver, sink]
+            .into_iter()
+            .filter_map(|pin| self.net_of(pin))
+            .flat_map(|net| net.drivers.iter().copied())
+            .chain([driver])
+            .collect::<HashSet<_>>();
+        if drivers.len() > 1
+            && !drivers
+                .iter()
+                .all(|pin| self.components[pin.0].borrow().can_float())
+        {
+            return Err(ConnectionError::AlreadyDriven);
+        }
+
+        let (driver, sink) = (self.pin_width(driver), self.pin_width(sink));
+        if driver != sink {
+            return Err(ConnectionError::WidthMismatch { driver, sink });
+        }
+
+        Ok(())
+    }
+
+    /// Wires `driver` to `sink`. If both pins are already on different nets, the nets are joined into one.
+    fn connect(&mut self, driver: RegisteredPin, sink: RegisteredPin) {
+        let id = match (
+            self.pin_nets.get(&driver).copied(),
+            self.pin_nets.get(&sink).copied(),
+        ) {
+            (Some(id), Some(other)) if id != other => {
+                let other = self.nets.remove(&other).unwrap();
+                for pin in other.drivers.iter().chain(&other.sinks) {
+                    self.pin_nets.insert(*pin, id);
+                }
+
+                let net = self.nets.get_mut(&id).unwrap();
+                net.drivers.extend(other.drivers);
+                net.sinks.extend(other.sinks);
+                net.wires.extend(other.wires);
+                id
+            }
+            (Some(id), _) | (None, Some(id)) => id,
+            (None, None) => {
+                let id = NetId(self.next_net);
+                self.next_net += 1;
+
+                self.nets.insert(
+                    id,
+                    Net {
+                        drivers: Vec::new(),
+                        sinks: Vec::new(),
+                        wires: Vec::new(),
+                    },
+                );
+                id
+            }
+        };
+
+        let net = self.nets.get_mut(&id).unwrap();
+        if !net.drivers.contains(&driver) {
+            net.drivers.push(driver);
+        }
+        if !net.sinks.contains(&sink) {
+            net.sinks.push(sink);
+        }
+        if !net.wires.contains(&(driver, sink)) {
+            net.wires.push((driver, sink));
+        }
+
+        // A new driver can change what every sink on the net sees
+        self.dirty.extend(net.sinks.iter().map(|sink| sink.0));
+        self.pin_nets.insert(driver, id);
+        self.pin_nets.insert(sink, id);
+    }
+
+    /// Removes the wire from `driver` to `sink`. Pins left without wires read as invalid afterwards, and a net
+    /// that falls apart is split up.
+    fn disconnect(&mut self, driver: RegisteredPin, sink: RegisteredPin) {
+        let Some(id) = self.pin_nets.get(&sink).copied() else {
+            return;
+        };
+        if !self.nets[&id].wires.contains(&(driver, sink)) {
+            return;
+        }
+
+        let net = self.nets.remove(&id).unwrap();
+        for pin in net.drivers.iter().chain(&net.sinks) {
+            self.pin_nets.remove(pin);
+        }
+        self.dirty.extend(net.sinks.iter().map(|sink| sink.0));
+
+        // Wiring the rest back up again builds whatever nets are left
+        for wire in net.wires {
+            if wire != (driver, sink) {
+                self.connect(wire.0, wire.1);
+            }
+        }
+    }
+
+    /// Number of bits carried by a pin
+    fn pin_width(&self, pin: RegisteredPin) -> usize {
+        self.components[pin.0].borrow().pin_width(pin.1)
+    }
+
+    /// Value currently driven onto an output pin
+    fn get_pin(&self, pin: RegisteredPin) -> StateVec {
+        self.pin_states
+            .get(&pin)
+            .cloned()
+            .unwrap_or_else(|| StateVec::new(self.pin_width(pin), Invalid))
+    }
+
+    fn set_input(&mut self, pin: RegisteredPin, value: impl Into<StateVec>) {
+        let component = &self.components[pin.0];
+        let mut component = component.borrow_mut();
+
+        component.set_input(pin.1, value.into());
+        self.dirty.insert(pin.0);
+    }
+
+    /// Gives an `Input` component a new value. Returns the value it had before, or None if the component isn't an
+    /// input.
+    fn replace_input(&mut self, handle: ComponentHandle, value: StateVec) -> Option<StateVec> {
+        let previous = match &*self.components[handle.0].borrow() {
+            Component::Input(previous) => previous.clone(),
+            _ => return None,
+        };
+
+        self.set_input(handle.to_pin(0), value);
+        Some(previous)
+    }
+
+    /// Inverts every bit of an `Input` component. Invalid and floating bits become high, so toggling twice doesn't
+    /// always restore the old value. Returns the value it had before, or None if the component isn't an input.
+    fn toggle_input(&mut self, handle: ComponentHandle) -> Option<StateVec> {
+        let value: StateVec = match &*self.components[handle.0].borrow() {
+            Component::Input(value) => value
+                .bits()
+                .iter()
+                .map(|bit| match bit {
+                    High => Low,
+                    Low | Invalid | HighZ => High,
+                })
+                .collect(),
+            _ => return None,
+        };
+
+        self.replace_input(handle, value)
+    }
+
+    /// Holds a push button down or lets go of it. Returns false if the component isn't a push button.
+    fn press_button(&mut self, handle: ComponentHandle, pressed: bool) -> bool {
+        match &mut *self.components[handle.0].borrow_mut() {
+            Component::PushButton(value) => *value = if pressed { High } else { Low },
+            _ => return false,
+        }
+
+        self.dirty.insert(handle.0);
+        true
+    }
+
+    /// Flips a toggle switch, or one bit of a DIP switch. Returns false if the component isn't a switch or doesn't
+    /// have that bit.
+    fn flip_switch(&mut self, handle: ComponentHandle, bit: usize) -> bool {
+        let flip = |value: &mut State| {
+            *value = match value {
+                High => Low,
+                Low | Invalid | HighZ => High,
+            }
+        };
+
+        match &mut *self.components[handle.0].borrow_mut() {
+            Component::ToggleSwitch(value) if bit == 0 => flip(value),
+            Component::DipSwitch(value) if bit < value.width() => flip(&mut value.0[bit]),
+            _ => return false,
+        }
+
+        self.dirty.insert(handle.0);
+        true
+    }
+
+    /// Runs `f` on the contents of a ROM or RAM component. Returns None if the component isn't a memory.
+    fn with_memory<R>(&self, handle: ComponentHandle, f: impl FnOnce(&Memory) -> R) -> Option<R> {
+        match &*self.components[handle.0].borrow() {
+            Component::Rom(memory) | Component::Ram(memory) => Some(f(memory)),
+            _ => None,
+        }
+    }
+
+    /// Changes the contents of a ROM or RAM component and schedules it to output the new words
+    fn edit_memory<R>(
+        &mut self,
+        handle: ComponentHandle,
+        f: impl FnOnce(&mut Memory) -> R,
+    ) -> Option<R> {
+        let result = match &mut *self.components[handle.0].borrow_mut() {
+            Component::Rom(memory) | Component::Ram(memory) => f(memory),
+            _ => return None,
+        };
+
+        self.dirty.insert(handle.0);
+        Some(result)
+    }
+
+    fn inspect_pin(&self, pin: RegisteredPin) -> StateVec {
+        let component = &self.components[pin.0];
+        let mut component = component.borrow_mut();
+
+        component.inspect_pin(pin.1)
+    }
+
+    fn time(&self) -> SimTime {
+        self.time
+    }
+
+    fn delay(&self, handle: ComponentHandle) -> SimTime {
+        self.delays
+            .get(&handle.0)
+            .copied()
+            .unwrap_or_else(|| self.components[handle.0].borrow().default_delay())
+    }
+
+    fn set_delay(&mut self, handle: ComponentHandle, delay: SimTime) {
+        self.delays.insert(handle.0, delay);
+    }
+
+    /// Value seen by an input pin of the given width, which is what the drivers of its net currently output.
+    /// Unconnected pins read as invalid.
+    fn input_state(&self, pin: RegisteredPin, width: usize) -> StateVec {
+        match self.net_of(pin) {
+            Some(net) => self.net_value(net, width),
+            None => StateVec::new(width, Invalid),
+        }
+    }
+
+    fn schedule(&mut self, time: SimTime, pin: RegisteredPin, value: StateVec) {
+        self.events.entry(time).or_default().push((pin, value));
+    }
+
+    /// Values currently seen by the input pins of a component
+    fn component_inputs(&self, index: usize) -> Vec<StateVec> {
+        let component = self.components[index].borrow();
+
+        (0..component.input_len())
+            .map(|pin| self.input_state(RegisteredPin(index, pin), component.pin_width(pin)))
+            .collect()
+    }
+
+    /// Runs a component on the current input values and returns the new values of its output pins
+    fn compute_outputs(&self, index: usize) -> Vec<(RegisteredPin, StateVec)> {
+        let inputs = self.component_inputs(index);
+        self.compute_outputs_from(index, &inputs)
+    }
+
+    /// Runs a component on the given input values and returns the new values of its output pins
+    fn compute_outputs_from(
+        &self,
+        index: usize,
+        inputs: &[StateVec],
+    ) -> Vec<(RegisteredPin, StateVec)> {
+        self.components[index]
+            .borrow_mut()
+            .evaluate(inputs)
+            .into_iter()
+            .enumerate()
+            // simulator i/o pins share the same indicies so add input length as offset for outputs
+            .map(|(pin, value)| (RegisteredPin(index, inputs.len() + pin), value))
+            .collect()
+    }
+
+    /// Evaluates a component with the current input values and schedules the new output values after its delay
+    fn evaluate(&mut self, index: usize) {
+        let time = self.time + self.delay(ComponentHandle(index));
+
+        for (pin, value) in self.compute_outputs(index) {
+            self.schedule(time, pin, value);
+        }
+    }
+
+    /// Evaluates a component and applies its outputs immediately. Returns whether any output changed.
+    fn evaluate_now(&mut self, index: usize) -> bool {
+        let mut changed = false;
+
+        for (pin, value) in self.compute_outputs(index) {
+            changed |= self.pin_states.insert(pin, value.clone()) != Some(value);
+        }
+
+        changed
+    }
+
+    /// Time of the next pending event, or `None` if the circuit is quiescent
+    fn next_event_time(&self) -> Option<SimTime> {
+        if self.dirty.is_empty() {
+            self.events.keys().next().copied()
+        } else {
+            Some(self.time)
+        }
+    }
+
+    /// Applies the earliest batch of pin changes and evaluates every component they affect.
+    /// Returns the time the step happened at, or `None` if there was nothing to do.
+    fn step(&mut self) -> Option<SimTime> {
+        if self.dirty.is_empty() {
+            let (time, changes) = self.events.pop_first()?;
+            self.time = time;
+
+            for (pin, value) in changes {
+                if self.pin_states.insert(pin, value.clone()) == Some(value) {
+                    continue;
+                }
+
+                if let Some(id) = self.pin_nets.get(&pin) {
+                    self.dirty
+                        .extend(self.nets[id].sinks.iter().map(|sink| sink.0));
+                }
+            }
+        }
+
+        for index in std::mem::take(&mut self.dirty) {
+            self.evaluate(index);
+        }
+
+        Some(self.time)
+    }
+
+    /// Steps until no events are left. Returns the time the circuit settled at.
+    fn run_until_quiescent(&mut self) -> Result<SimTime, SimError> {
+        for _ in 0..Self::STEP_LIMIT {
+            if self.step().is_none() {
+                return Ok(self.time);
+            }
+        }
+
+        Err(SimError::Unsettled { time: self.time })
+    }
+
+    /// Processes every event up to and including `time`, then moves the clock to `time`
+    fn run_until(&mut self, time: SimTime) -> Result<(), SimError> {
+        for _ in 0..Self::STEP_LIMIT {
+            match self.next_event_time() {
+                Some(next) if next <= time => {
+                    self.step();
+                }
+                _ => {
+                    self.time = self.time.max(time);
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(SimError::Unsettled { time: self.time })
+    }
+
+    /// Sets every clock to its level at the current time and marks the ones that changed for evaluation
+    fn update_clocks(&mut self) {
+        for (index, component) in self.components.iter().enumerate() {
+            if let Component::Clock(clock) = &mut *component.borrow_mut() {
+                let value = clock.level_at(self.time);
+                if clock.value != value {
+                    clock.value = value;
+                    self.dirty.insert(index);
+                }
+            }
+        }
+    }
+
+    /// Earliest time after now where any clock changes
+    fn next_clock_edge(&self) -> Option<SimTime> {
+        self.components
+            .iter()
+            .filter_map(|component| match &*component.borrow() {
+                Component::Clock(clock) => clock.next_edge(self.time),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Moves simulated time forward by `duration`, processing events and toggling clocks on the way
+    fn advance(&mut self, duration: SimTime) -> Result<(), SimError> {
+        let end = self.time + duration;
+
+        loop {
+            match self.next_clock_edge() {
+                Some(edge) if edge <= end => {
+                    self.run_until(edge)?;
+                    self.update_clocks();
+                }
+                _ => return self.run_until(end),
+            }
+        }
+    }
+
+    /// Groups the components into strongly connected components of the connection graph, using Tarjan's algorithm.
+    /// Groups come out in topological order, so every group only depends on the ones before it.
+    fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'a> {
+            edges: &'a HashMap<usize, Vec<usize>>,
+            index: usize,
+            indices: HashMap<usize, usize>,
+            low_links: HashMap<usize, usize>,
+            stack: Vec<usize>,
+            on_stack: HashSet<usize>,
+            groups: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, node: usize) {
+                self.indices.insert(node, self.index);
+                self.low_links.insert(node, self.index);
+                self.index += 1;
+                self.stack.push(node);
+                self.on_stack.insert(node);
+
+                for &next in self.edges.get(&node).into_iter().flatten() {
+                    if !self.indices.contains_key(&next) {
+                        self.visit(next);
+                        let low = self.low_links[&node].min(self.low_links[&next]);
+                        self.low_links.insert(node, low);
+                    } else if self.on_stack.contains(&next) {
+                        let low = self.low_links[&node].min(self.indices[&next]);
+                        self.low_links.insert(node, low);
+                    }
+                }
+
+                if self.low_links[&node] == self.indices[&node] {
+                    let mut group = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack.remove(&member);
+                        group.push(member);
+
+                        if member == node {
+                            break;
+                        }
+                    }
+
+                    group.sort_unstable();
+                    self.groups.push(group);
+                }
+            }
+        }
+
+        let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (driver, sink) in self.links() {
+            edges.entry(driver.0).or_default().push(sink.0);
+        }
+        edges.values_mut().for_each(|sinks| sinks.sort_unstable());
+
+        let mut tarjan = Tarjan {
+            edges: &edges,
+            index: 0,
+            indices: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            groups: Vec::new(),
+        };
+
+        for node in 0..self.components.len() {
+            if !tarjan.indices.contains_key(&node) {
+                tarjan.visit(node);
+            }
+        }
+
+        // Tarjan emits groups sinks first
+        tarjan.groups.reverse();
+        tarjan.groups
+    }
+
+    /// Whether a group of components contains a feedback loop
+    fn is_cyclic(&self, group: &[usize]) -> bool {
+        group.len() > 1
+            || self
+                .links()
+                .any(|(driver, sink)| driver.0 == group[0] && sink.0 == group[0])
+    }
+
+    /// Evaluates the whole circuit with zero delays, ignoring any pending events. Feedback loops are iterated
+    /// until their outputs stop changing; loops that never converge have their outputs forced to `Invalid`.
+    ///
+    /// Edge-triggered components all sample their inputs before any of them changes, like flip-flops sharing a
+    /// clock: the circuit is first settled around their old outputs, then they're clocked all at once, and then
+    /// their new outputs are settled.
+    fn settle(&mut self) -> Result<(), SimError> {
+        self.update_clocks();
+        self.events.clear();
+        self.dirty.clear();
+
+        let edge_triggered = (0..self.components.len())
+            .filter(|index| self.components[*index].borrow().is_edge_triggered())
+            .collect::<HashSet<_>>();
+        let mut oscillating = self.settle_groups(&edge_triggered);
+
+        if !edge_triggered.is_empty() {
+            let sampled = edge_triggered
+                .iter()
+                .map(|&index| (index, self.component_inputs(index)))
+                .collect::<Vec<_>>();
+            for (index, inputs) in sampled {
+                for (pin, value) in self.compute_outputs_from(index, &inputs) {
+                    self.pin_states.insert(pin, value);
+                }
+            }
+
+            for handle in self.settle_groups(&HashSet::new()) {
+                if !oscillating.contains(&handle) {
+                    oscillating.push(handle);
+                }
+            }
+        }
+
+        if oscillating.is_empty() {
+            Ok(())
+        } else {
+            Err(SimError::Oscillation {
+                components: oscillating,
+            })
+        }
+    }
+
+    /// One pass of `settle` that leaves the `frozen` components alone. Returns the components of feedback loops
+    /// that didn't converge.
+    fn settle_groups(&mut self, frozen: &HashSet<usize>) -> Vec<ComponentHandle> {
+        let mut oscillating = Vec::new();
+
+        for mut group in self.strongly_connected_components() {
+            group.retain(|index| !frozen.contains(index));
+            if group.is_empty() {
+                continue;
+            }
+
+            if !self.is_cyclic(&group) {
+                self.evaluate_now(group[0]);
+                continue;
+            }
+
+            let mut converged = false;
+            for _ in 0..Self::ITERATION_LIMIT {
+                let mut changed = false;
+                for &index in &group {
+                    changed |= self.evaluate_now(index);
+                }
+
+                if !changed {
+                    converged = true;
+                    break;
+                }
+            }
+
+            if !converged {
+                for &index in &group {
+                    for (pin, value) in self.compute_outputs(index) {
+                        self.pin_states
+                            .insert(pin, StateVec::new(value.width(), Invalid));
+                    }
+                }
+
+                oscillating.extend(group.into_iter().map(ComponentHandle));
+            }
+        }
+
+        oscillating
+    }
+
+    /// Processes the pending events, so changed inputs propagate through the circuit with their delays
+    fn tick(&mut self) {
+        if let Err(err) = self.run_until_quiescent() {
+            eprintln!("WARNING: {err}");
+        }
+    }
+
+    /// Brings a freshly loaded or structurally changed circuit into a consistent state, see `settle`
+    fn initialize(&mut self) {
+        if let Err(err) = self.settle() {
+            eprintln!("WARNING: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    fn test_or() {
+    fn test_or() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+
+        let or_index = sim.insert_component(Component::OrGate(2));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(or_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(or_index, 1));
+        sim.connect(RegisteredPin(or_index, 2), RegisteredPin(output_index, 0));
+
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), Low);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+    }
+
+    #[test]
+    fn test_and() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+
+        let gate_index = sim.insert_component(Component::AndGate(2));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(gate_index, 1));
+        sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(output_index, 0));
+
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), Low);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+    }
+
+    #[test]
+    fn test_xor() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+
+        let gate_index = sim.insert_component(Component::XorGate(2));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(gate_index, 1));
+        sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(output_index, 0));
+
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), Low);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            High
+        );
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
+            Low,
+        );
+    }
+
+    /// Runs a gate over every combination of low and high inputs and checks the output against `expected`,
+    /// which gets the number of high inputs
+    fn check_gate(component: Component, expected: impl Fn(usize) -> State) {
+        let mut sim = Simulator::new();
+
+        let inputs_len = component.input_len();
+        let gate = sim.insert_component(component);
+        let inputs = (0..inputs_len)
+            .map(|pin| {
+                let input = sim.insert_component(Component::Input(Low.into()));
+                sim.connect(RegisteredPin(input, 0), RegisteredPin(gate, pin));
+                input
+            })
+            .collect::<Vec<_>>();
+        let output = sim.insert_component(Component::Output(Low.into()));
+        sim.connect(RegisteredPin(gate, inputs_len), RegisteredPin(output, 0));
+        sim.initialize();
+
+        for combination in 0..1usize << inputs_len {
+            for (bit, input) in inputs.iter().enumerate() {
+                let value = if combination >> bit & 1 == 1 {
+                    High
+                } else {
+                    Low
+                };
+                sim.set_input(RegisteredPin(*input, 0), value);
+            }
+
+            sim.tick();
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(output, 0)),
+                expected(combination.count_ones() as usize),
+                "inputs {combination:b}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_nand_nor_xnor() {
+        let level = |high: bool| if high { High } else { Low };
+
+        check_gate(Component::NandGate(2), |high| level(high < 2));
+        check_gate(Component::NorGate(2), |high| level(high == 0));
+        check_gate(Component::XnorGate(2), |high| level(high % 2 == 0));
+        check_gate(Component::BufferGate, |high| level(high == 1));
+    }
+
+    #[test]
+    fn test_wide_gates() {
+        let level = |high: bool| if high { High } else { Low };
+
+        for inputs in [3, 5] {
+            check_gate(Component::AndGate(inputs), |high| level(high == inputs));
+            check_gate(Component::OrGate(inputs), |high| level(high > 0));
+            check_gate(Component::XorGate(inputs), |high| level(high % 2 == 1));
+            check_gate(Component::NandGate(inputs), |high| level(high < inputs));
+            check_gate(Component::NorGate(inputs), |high| level(high == 0));
+            check_gate(Component::XnorGate(inputs), |high| level(high % 2 == 0));
+        }
+
+        assert_eq!(Component::OrGate(32).input_len(), 32);
+    }
+
+    #[test]
+    fn test_controlling_values() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let in2 = sim.insert_component(Component::Input(High.into()));
+        // The third input is left invalid
+        let in3 = sim.insert_component(Component::Input(Invalid.into()));
+
+        let gate_index = sim.insert_component(Component::AndGate(3));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(gate_index, 1));
+        sim.connect(RegisteredPin(in3, 0), RegisteredPin(gate_index, 2));
+        sim.connect(RegisteredPin(gate_index, 3), RegisteredPin(output_index, 0));
+
+        sim.initialize();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Invalid);
+
+        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let value = StateVec::from_number(6, 4);
+
+        assert_eq!(value.to_string(), "0110");
+        assert_eq!(value.to_number(), Some(6));
+        assert_eq!(StateVec::from_number(0b10110, 3).to_string(), "110");
+        assert_eq!(StateVec::from(HighZ).to_string(), "Z");
+        assert_eq!(StateVec::from(vec![High, Invalid]).to_number(), None);
+        assert_eq!(StateVec::from(vec![HighZ]).to_number(), None);
+    }
+
+    #[test]
+    fn test_multiplexer() {
+        let mut sim = Simulator::new();
+
+        let mux = sim.insert_component(Component::Multiplexer {
+            select: 2,
+            width: 2,
+        });
+
+        // Every data input holds its own index
+        for pin in 0..4 {
+            let data = sim.insert_component(Component::Input(StateVec::from_number(pin as u64, 2)));
+            sim.connect(RegisteredPin(data, 0), RegisteredPin(mux, pin));
+        }
+
+        let select = sim.insert_component(Component::Input(StateVec::new(2, Low)));
+        let output = sim.insert_component(Component::Output(StateVec::new(2, Low)));
+
+        sim.connect(RegisteredPin(select, 0), RegisteredPin(mux, 4));
+        sim.connect(RegisteredPin(mux, 5), RegisteredPin(output, 0));
+
+        sim.initialize();
+
+        for index in 0..4 {
+            sim.set_input(RegisteredPin(select, 0), StateVec::from_number(index, 2));
+            sim.tick();
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(output, 0)).to_number(),
+                Some(index)
+            );
+        }
+
+        sim.set_input(RegisteredPin(select, 0), StateVec::from(vec![Low, Invalid]));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "XX");
+    }
+
+    #[test]
+    fn test_demultiplexer() {
+        let mut sim = Simulator::new();
+
+        let data = sim.insert_component(Component::Input(Low.into()));
+        let select = sim.insert_component(Component::Input(StateVec::new(2, Low)));
+        let demux = sim.insert_component(Component::Demultiplexer {
+            select: 2,
+            width: 1,
+        });
+
+        sim.connect(RegisteredPin(data, 0), RegisteredPin(demux, 0));
+        sim.connect(RegisteredPin(select, 0), RegisteredPin(demux, 1));
+
+        let outputs = (0..4)
+            .map(|i| {
+                let output = sim.insert_component(Component::Output(Low.into()));
+                sim.connect(RegisteredPin(demux, 2 + i), RegisteredPin(output, 0));
+                output
+            })
+            .collect::<Vec<_>>();
+
+        sim.initialize();
+
+        // select, data, outputs 0 to 3
+        let table = [
+            (0, Low, [Low, Low, Low, Low]),
+            (0, High, [High, Low, Low, Low]),
+            (1, High, [Low, High, Low, Low]),
+            (2, High, [Low, Low, High, Low]),
+            (3, High, [Low, Low, Low, High]),
+            (3, Low, [Low, Low, Low, Low]),
+        ];
+
+        for (index, value, expected) in table {
+            sim.set_input(RegisteredPin(select, 0), StateVec::from_number(index, 2));
+            sim.set_input(RegisteredPin(data, 0), value);
+            sim.tick();
+
+            for (output, expected) in outputs.iter().zip(expected) {
+                assert_eq!(sim.inspect_pin(RegisteredPin(*output, 0)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut sim = Simulator::new();
+
+        let input = sim.insert_component(Component::Input(StateVec::new(3, Low)));
+        let decoder = sim.insert_component(Component::Decoder(3));
+        assert_eq!(Component::Decoder(3).output_len(), 8);
+
+        sim.connect(RegisteredPin(input, 0), RegisteredPin(decoder, 0));
+
+        let outputs = (0..8)
+            .map(|i| {
+                let output = sim.insert_component(Component::Output(Low.into()));
+                sim.connect(RegisteredPin(decoder, 1 + i), RegisteredPin(output, 0));
+                output
+            })
+            .collect::<Vec<_>>();
+
+        sim.initialize();
+
+        for index in 0..8 {
+            sim.set_input(RegisteredPin(input, 0), StateVec::from_number(index, 3));
+            sim.tick();
+
+            for (output_index, output) in outputs.iter().enumerate() {
+                let expected = if output_index as u64 == index {
+                    High
+                } else {
+                    Low
+                };
+                assert_eq!(sim.inspect_pin(RegisteredPin(*output, 0)), expected);
+            }
+        }
+
+        sim.set_input(
+            RegisteredPin(input, 0),
+            StateVec::from(vec![Invalid, Low, Low]),
+        );
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(outputs[0], 0)), Invalid);
+    }
+
+    #[test]
+    fn test_priority_encoder() {
+        let mut sim = Simulator::new();
+
+        let encoder = sim.insert_component(Component::PriorityEncoder(2));
+
+        let inputs = (0..4)
+            .map(|pin| {
+                let input = sim.insert_component(Component::Input(Low.into()));
+                sim.connect(RegisteredPin(input, 0), RegisteredPin(encoder, pin));
+                input
+            })
+            .collect::<Vec<_>>();
+
+        let index = sim.insert_component(Component::Output(StateVec::new(2, Low)));
+        let valid = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(encoder, 4), RegisteredPin(index, 0));
+        sim.connect(RegisteredPin(encoder, 5), RegisteredPin(valid, 0));
+
+        sim.initialize();
+
+        // inputs 0 to 3, index, valid
+        let table = [
+            ([Low, Low, Low, Low], "00", Low),
+            ([High, Low, Low, Low], "00", High),
+            ([High, High, Low, Low], "01", High),
+            ([Low, High, High, Low], "10", High),
+            ([High, Low, High, High], "11", High),
+            ([Invalid, Low, High, Low], "10", High),
+            ([Low, Low, High, Invalid], "XX", Invalid),
+        ];
+
+        for (values, expected_index, expected_valid) in table {
+            for (input, value) in inputs.iter().zip(values) {
+                sim.set_input(RegisteredPin(*input, 0), value);
+            }
+
+            sim.tick();
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(index, 0)).to_string(),
+                expected_index
+            );
+            assert_eq!(sim.inspect_pin(RegisteredPin(valid, 0)), expected_valid);
+        }
+    }
+
+    #[test]
+    fn test_adder() {
+        let mut sim = Simulator::new();
+
+        let a = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let b = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let carry_in = sim.insert_component(Component::Input(Low.into()));
+        let adder = sim.insert_component(Component::Adder(4));
+        let sum = sim.insert_component(Component::Output(StateVec::new(4, Low)));
+        let carry_out = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(a, 0), RegisteredPin(adder, 0));
+        sim.connect(RegisteredPin(b, 0), RegisteredPin(adder, 1));
+        sim.connect(RegisteredPin(carry_in, 0), RegisteredPin(adder, 2));
+        sim.connect(RegisteredPin(adder, 3), RegisteredPin(sum, 0));
+        sim.connect(RegisteredPin(adder, 4), RegisteredPin(carry_out, 0));
+
+        sim.initialize();
+
+        // a, b, carry in, sum, carry out
+        let table = [
+            (0, 0, Low, 0, Low),
+            (3, 4, Low, 7, Low),
+            (3, 4, High, 8, Low),
+            (9, 7, Low, 0, High),
+            (15, 15, High, 15, High),
+        ];
+
+        for (a_value, b_value, carry, expected_sum, expected_carry) in table {
+            sim.set_input(RegisteredPin(a, 0), StateVec::from_number(a_value, 4));
+            sim.set_input(RegisteredPin(b, 0), StateVec::from_number(b_value, 4));
+            sim.set_input(RegisteredPin(carry_in, 0), carry);
+            sim.tick();
+
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(sum, 0)).to_number(),
+                Some(expected_sum)
+            );
+            assert_eq!(sim.inspect_pin(RegisteredPin(carry_out, 0)), expected_carry);
+        }
+
+        sim.set_input(
+            RegisteredPin(a, 0),
+            StateVec::from(vec![Low, Invalid, Low, Low]),
+        );
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(sum, 0)).to_string(), "XXXX");
+        assert_eq!(sim.inspect_pin(RegisteredPin(carry_out, 0)), Invalid);
+    }
+
+    #[test]
+    fn test_subtractor() {
+        let mut sim = Simulator::new();
+
+        let a = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let b = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let borrow_in = sim.insert_component(Component::Input(Low.into()));
+        let subtractor = sim.insert_component(Component::Subtractor(4));
+        let difference = sim.insert_component(Component::Output(StateVec::new(4, Low)));
+        let borrow_out = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(a, 0), RegisteredPin(subtractor, 0));
+        sim.connect(RegisteredPin(b, 0), RegisteredPin(subtractor, 1));
+        sim.connect(RegisteredPin(borrow_in, 0), RegisteredPin(subtractor, 2));
+        sim.connect(RegisteredPin(subtractor, 3), RegisteredPin(difference, 0));
+        sim.connect(RegisteredPin(subtractor, 4), RegisteredPin(borrow_out, 0));
+
+        sim.initialize();
+
+        // a, b, borrow in, difference, borrow out
+        let table = [
+            (7, 3, Low, 4, Low),
+            (7, 3, High, 3, Low),
+            (3, 3, Low, 0, Low),
+            (3, 4, Low, 15, High),
+            (0, 0, High, 15, High),
+        ];
+
+        for (a_value, b_value, borrow, expected_difference, expected_borrow) in table {
+            sim.set_input(RegisteredPin(a, 0), StateVec::from_number(a_value, 4));
+            sim.set_input(RegisteredPin(b, 0), StateVec::from_number(b_value, 4));
+            sim.set_input(RegisteredPin(borrow_in, 0), borrow);
+            sim.tick();
+
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(difference, 0)).to_number(),
+                Some(expected_difference)
+            );
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(borrow_out, 0)),
+                expected_borrow
+            );
+        }
+
+        sim.set_input(RegisteredPin(borrow_in, 0), Invalid);
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(difference, 0)).to_string(),
+            "XXXX"
+        );
+    }
+
+    #[test]
+    fn test_comparator() {
+        let mut sim = Simulator::new();
+
+        let a = sim.insert_component(Component::Input(StateVec::new(8, Low)));
+        let b = sim.insert_component(Component::Input(StateVec::new(8, Low)));
+        let comparator = sim.insert_component(Component::Comparator(8));
+
+        sim.connect(RegisteredPin(a, 0), RegisteredPin(comparator, 0));
+        sim.connect(RegisteredPin(b, 0), RegisteredPin(comparator, 1));
+
+        // Less than, equal, greater than
+        let outputs = (2..5)
+            .map(|pin| {
+                let output = sim.insert_component(Component::Output(Low.into()));
+                sim.connect(RegisteredPin(comparator, pin), RegisteredPin(output, 0));
+                output
+            })
+            .collect::<Vec<_>>();
+
+        sim.initialize();
+
+        // a, b, less than, equal, greater than
+        let table = [
+            (0, 0, [Low, High, Low]),
+            (1, 200, [High, Low, Low]),
+            (200, 1, [Low, Low, High]),
+            (255, 255, [Low, High, Low]),
+        ];
+
+        for (a_value, b_value, expected) in table {
+            sim.set_input(RegisteredPin(a, 0), StateVec::from_number(a_value, 8));
+            sim.set_input(RegisteredPin(b, 0), StateVec::from_number(b_value, 8));
+            sim.tick();
+
+            for (output, expected) in outputs.iter().zip(expected) {
+                assert_eq!(sim.inspect_pin(RegisteredPin(*output, 0)), expected);
+            }
+        }
+
+        sim.set_input(RegisteredPin(b, 0), StateVec::new(8, HighZ));
+        sim.tick();
+        for output in &outputs {
+            assert_eq!(sim.inspect_pin(RegisteredPin(*output, 0)), Invalid);
+        }
+    }
+
+    #[test]
+    fn test_multiplier() {
+        let mut sim = Simulator::new();
+
+        let a = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let b = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let multiplier = sim.insert_component(Component::Multiplier(4));
+        let product = sim.insert_component(Component::Output(StateVec::new(8, Low)));
+        assert_eq!(sim.pin_width(RegisteredPin(multiplier, 2)), 8);
+
+        sim.connect(RegisteredPin(a, 0), RegisteredPin(multiplier, 0));
+        sim.connect(RegisteredPin(b, 0), RegisteredPin(multiplier, 1));
+        sim.connect(RegisteredPin(multiplier, 2), RegisteredPin(product, 0));
+
+        sim.initialize();
+
+        for (a_value, b_value) in [(0, 9), (3, 5), (15, 15), (12, 11)] {
+            sim.set_input(RegisteredPin(a, 0), StateVec::from_number(a_value, 4));
+            sim.set_input(RegisteredPin(b, 0), StateVec::from_number(b_value, 4));
+            sim.tick();
+
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(product, 0)).to_number(),
+                Some(a_value * b_value)
+            );
+        }
+
+        sim.set_input(
+            RegisteredPin(a, 0),
+            StateVec::from(vec![Invalid, Low, Low, Low]),
+        );
+        sim.tick();
+        assert_eq!(
+            sim.inspect_pin(RegisteredPin(product, 0)).to_string(),
+            "XXXXXXXX"
+        );
+    }
+
+    #[test]
+    fn test_toggle_input() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+
+        assert_eq!(sim.toggle_input(ComponentHandle(in1)), Some(Low.into()));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        assert_eq!(sim.toggle_input(ComponentHandle(in1)), Some(High.into()));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), High);
+
+        // Invalid bits toggle to high, only putting back the old value undoes that
+        sim.replace_input(ComponentHandle(in1), Invalid.into());
+        assert_eq!(sim.toggle_input(ComponentHandle(in1)), Some(Invalid.into()));
+        assert_eq!(
+            sim.replace_input(ComponentHandle(in1), Invalid.into()),
+            Some(High.into())
+        );
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Invalid);
+
+        // Only inputs can be toggled
+        assert_eq!(sim.toggle_input(ComponentHandle(gate_index)), None);
+    }
+
+    #[test]
+    fn test_remove_component() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+        sim.tick();
+
+        let connections = sim.connections_of(ComponentHandle(gate_index));
+        let gate = sim.remove_component(ComponentHandle(gate_index));
+        sim.tick();
+
+        assert!(sim.nets.is_empty());
+        assert!(sim.pin_nets.is_empty());
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Invalid);
+
+        // The other handles still refer to the same components
+        assert_eq!(sim.inspect_pin(RegisteredPin(in1, 0)), High);
+
+        sim.restore_component(ComponentHandle(gate_index), gate);
+        for (driver, sink) in connections {
+            sim.connect(driver, sink);
+        }
+        sim.tick();
+
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), High);
+
+        // The output is already driven by the gate
+        assert_eq!(
+            sim.check_connection(RegisteredPin(in1, 0), RegisteredPin(output_index, 0)),
+            Err(ConnectionError::AlreadyDriven)
+        );
+
+        sim.disconnect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.tick();
+
+        assert_eq!(sim.connections().count(), 1);
+        assert_eq!(sim.pin_nets.len(), 2);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Invalid);
+        assert_eq!(
+            sim.check_connection(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0)),
+            Ok(())
+        );
+
+        // Disconnecting pins that aren't wired together does nothing
+        sim.disconnect(RegisteredPin(in1, 0), RegisteredPin(output_index, 0));
+        assert_eq!(sim.connections().count(), 1);
+    }
+
+    #[test]
+    fn test_stable_handles() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(output_index, 0));
+        sim.remove_component(ComponentHandle(in1));
+
+        assert_eq!(sim.input_components, vec![in2]);
+        assert_eq!(sim.output_components, vec![output_index]);
+
+        // Removed slots are never reused
+        let in3 = sim.insert_component(Component::Input(High.into()));
+        assert_ne!(in3, in1);
+
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        // A removed component has no pins left to connect
+        assert_eq!(
+            sim.check_connection(RegisteredPin(in1, 0), RegisteredPin(output_index, 0)),
+            Err(ConnectionError::NotAnOutput)
+        );
+    }
+
+    #[test]
+    fn test_extract_merge() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.set_delay(ComponentHandle(gate_index), 3);
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+
+        // Only the wire between the copied components comes along
+        let (copy, ids) = sim.extract(&[ComponentHandle(in1), ComponentHandle(gate_index)]);
+        assert_eq!(copy.components.len(), 2);
+        assert_eq!(copy.connections().count(), 1);
+        assert_eq!(copy.delay(ComponentHandle(ids[&gate_index])), 3);
+
+        let ids = sim.merge(&copy);
+        assert_eq!(sim.components.len(), 5);
+        assert_eq!(sim.connections().count(), 3);
+        assert_eq!(sim.delay(ComponentHandle(ids[&1])), 3);
+
+        // The pasted not gate can drive its own output
+        let output2 = sim.insert_component(Component::Output(Low.into()));
+        sim.connect(RegisteredPin(ids[&1], 1), RegisteredPin(output2, 0));
+        sim.tick();
+
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output2, 0)), Low);
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let not1 = sim.insert_component(Component::NotGate);
+        let not2 = sim.insert_component(Component::NotGate);
+        let out1 = sim.insert_component(Component::Output(Low.into()));
+        let out2 = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(not1, 0));
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(not2, 0));
+        sim.connect(RegisteredPin(not1, 1), RegisteredPin(out1, 0));
+        sim.connect(RegisteredPin(not2, 1), RegisteredPin(out2, 0));
+
+        // Both sinks share the net of the input
+        let net = sim.net_of(RegisteredPin(in1, 0)).unwrap();
+        assert_eq!(net.drivers(), &[RegisteredPin(in1, 0)]);
+        assert_eq!(
+            net.sinks(),
+            &[RegisteredPin(not1, 0), RegisteredPin(not2, 0)]
+        );
+        assert_eq!(sim.nets().count(), 3);
+
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(out1, 0)), High);
+        assert_eq!(sim.inspect_pin(RegisteredPin(out2, 0)), High);
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.run_until_quiescent().unwrap();
+        assert_eq!(sim.inspect_pin(RegisteredPin(out1, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(out2, 0)), Low);
+
+        // Removing one branch leaves the other connected
+        sim.disconnect(RegisteredPin(in1, 0), RegisteredPin(not1, 0));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(out1, 0)), Invalid);
+        assert_eq!(sim.inspect_pin(RegisteredPin(out2, 0)), Low);
+
+        sim.disconnect(RegisteredPin(in1, 0), RegisteredPin(not2, 0));
+        assert!(sim.net_of(RegisteredPin(in1, 0)).is_none());
+        assert_eq!(sim.nets().count(), 2);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(HighZ, HighZ), HighZ);
+        assert_eq!(resolve(HighZ, High), High);
+        assert_eq!(resolve(Low, HighZ), Low);
+        assert_eq!(resolve(High, High), High);
+        assert_eq!(resolve(Low, High), Invalid);
+        assert_eq!(resolve(Invalid, HighZ), Invalid);
+    }
+
+    #[test]
+    fn test_tri_state_bus() {
+        let mut sim = Simulator::new();
+
+        let data1 = sim.insert_component(Component::Input(StateVec::from(vec![High, Low])));
+        let data2 = sim.insert_component(Component::Input(StateVec::from(vec![Low, Low])));
+        let enable1 = sim.insert_component(Component::Input(Low.into()));
+        let enable2 = sim.insert_component(Component::Input(Low.into()));
+        let buffer1 = sim.insert_component(Component::TriState(2));
+        let buffer2 = sim.insert_component(Component::TriState(2));
+        let output = sim.insert_component(Component::Output(StateVec::new(2, Low)));
+
+        sim.connect(RegisteredPin(data1, 0), RegisteredPin(buffer1, 0));
+        sim.connect(RegisteredPin(enable1, 0), RegisteredPin(buffer1, 1));
+        sim.connect(RegisteredPin(data2, 0), RegisteredPin(buffer2, 0));
+        sim.connect(RegisteredPin(enable2, 0), RegisteredPin(buffer2, 1));
+        sim.connect(RegisteredPin(buffer1, 2), RegisteredPin(output, 0));
+        sim.connect(RegisteredPin(buffer2, 2), RegisteredPin(output, 0));
+
+        // Both buffers drive the same net, but plain outputs can't join them
+        let net = sim.net_of(RegisteredPin(output, 0)).unwrap();
+        assert_eq!(net.drivers().len(), 2);
+        assert_eq!(
+            sim.check_connection(RegisteredPin(data1, 0), RegisteredPin(output, 0)),
+            Err(ConnectionError::AlreadyDriven)
+        );
+
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "ZZ");
+
+        sim.set_input(RegisteredPin(enable1, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "01");
+
+        sim.set_input(RegisteredPin(enable1, 0), Low);
+        sim.set_input(RegisteredPin(enable2, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "00");
+
+        // The buffers disagree on the low bit only
+        sim.set_input(RegisteredPin(enable1, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "0X");
+
+        // Taking one buffer off the net leaves the other one driving it
+        sim.disconnect(RegisteredPin(buffer2, 2), RegisteredPin(output, 0));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)).to_string(), "01");
+        assert!(sim.net_of(RegisteredPin(buffer2, 2)).is_none());
+    }
+
+    #[test]
+    fn test_floating_input() {
+        let mut sim = Simulator::new();
+
+        let enable = sim.insert_component(Component::Input(Low.into()));
+        let data = sim.insert_component(Component::Input(High.into()));
+        let buffer = sim.insert_component(Component::TriState(1));
+        let gate = sim.insert_component(Component::NotGate);
+        let output = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(data, 0), RegisteredPin(buffer, 0));
+        sim.connect(RegisteredPin(enable, 0), RegisteredPin(buffer, 1));
+        sim.connect(RegisteredPin(buffer, 2), RegisteredPin(gate, 0));
+        sim.connect(RegisteredPin(gate, 1), RegisteredPin(output, 0));
+
+        // Gates can't make sense of a wire nobody drives
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)), Invalid);
+
+        sim.set_input(RegisteredPin(enable, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output, 0)), Low);
+    }
+
+    #[test]
+    fn test_clock_edges() {
+        let clock = Clock::new(10, 3, 0.3);
+
+        assert_eq!(clock.level_at(0), Low);
+        assert_eq!(clock.level_at(3), High);
+        assert_eq!(clock.level_at(5), High);
+        assert_eq!(clock.level_at(6), Low);
+        assert_eq!(clock.level_at(13), High);
+
+        assert_eq!(clock.next_edge(0), Some(3));
+        assert_eq!(clock.next_edge(3), Some(6));
+        assert_eq!(clock.next_edge(6), Some(13));
+        assert_eq!(Clock::new(0, 0, 0.5).next_edge(0), None);
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut sim = Simulator::new();
+
+        let clock = sim.insert_component(Component::Clock(Clock::new(4, 0, 0.5)));
+        let not_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(not_index, 0));
+        sim.connect(RegisteredPin(not_index, 1), RegisteredPin(output_index, 0));
+        sim.initialize();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        // The not gate takes a time unit to follow each edge
+        let mut trace = Vec::new();
+        for _ in 0..8 {
+            sim.advance(1).unwrap();
+            trace.push(sim.inspect_pin(RegisteredPin(output_index, 0)).bit(0));
+        }
+
+        assert_eq!(trace, [Low, Low, High, High, Low, Low, High, High]);
+        assert_eq!(sim.time(), 8);
+    }
+
+    /// Raises and lowers a clock input, ticking after each change
+    fn pulse(sim: &mut Simulator, clock: usize) {
+        sim.set_input(RegisteredPin(clock, 0), High);
+        sim.tick();
+        sim.set_input(RegisteredPin(clock, 0), Low);
+        sim.tick();
+    }
+
+    #[test]
+    fn test_d_flip_flop() {
         let mut sim = Simulator::new();
 
-        let in1 = sim.insert_component(Component::Input(Low));
-        let in2 = sim.insert_component(Component::Input(Low));
+        let d = sim.insert_component(Component::Input(Low.into()));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let flip_flop = sim.insert_component(Component::DFlipFlop(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
+        let not_q = sim.insert_component(Component::Output(Low.into()));
 
-        let or_index = sim.insert_component(Component::OrGate);
-        let output_index = sim.insert_component(Component::Output(Low));
+        sim.connect(RegisteredPin(d, 0), RegisteredPin(flip_flop, 0));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(flip_flop, 1));
+        sim.connect(RegisteredPin(flip_flop, 2), RegisteredPin(q, 0));
+        sim.connect(RegisteredPin(flip_flop, 3), RegisteredPin(not_q, 0));
+        sim.initialize();
 
-        sim.connect(RegisteredPin(in1, 0), RegisteredPin(or_index, 0));
-        sim.connect(RegisteredPin(in2, 0), RegisteredPin(or_index, 1));
-        sim.connect(RegisteredPin(or_index, 2), RegisteredPin(output_index, 0));
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), High);
 
+        // D only gets stored on a rising edge
+        sim.set_input(RegisteredPin(d, 0), High);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
-        );
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
 
-        sim.set_input(RegisteredPin(in1, 0), Low);
-        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.set_input(RegisteredPin(clock, 0), High);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
-        );
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), Low);
 
-        sim.set_input(RegisteredPin(in1, 0), High);
-        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.set_input(RegisteredPin(d, 0), Low);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
-        );
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
 
-        sim.set_input(RegisteredPin(in1, 0), High);
-        sim.set_input(RegisteredPin(in2, 0), High);
+        sim.set_input(RegisteredPin(clock, 0), Low);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
-        );
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(clock, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), High);
     }
 
     #[test]
-    fn test_and() {
+    fn test_jk_flip_flop() {
         let mut sim = Simulator::new();
 
-        let in1 = sim.insert_component(Component::Input(Low));
-        let in2 = sim.insert_component(Component::Input(Low));
+        let j = sim.insert_component(Component::Input(Low.into()));
+        let k = sim.insert_component(Component::Input(Low.into()));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let flip_flop = sim.insert_component(Component::JkFlipFlop(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
+        let not_q = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(j, 0), RegisteredPin(flip_flop, 0));
+        sim.connect(RegisteredPin(k, 0), RegisteredPin(flip_flop, 1));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(flip_flop, 2));
+        sim.connect(RegisteredPin(flip_flop, 3), RegisteredPin(q, 0));
+        sim.connect(RegisteredPin(flip_flop, 4), RegisteredPin(not_q, 0));
+        sim.initialize();
+
+        sim.set_input(RegisteredPin(j, 0), High);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(k, 0), High);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(j, 0), Low);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        sim.set_input(RegisteredPin(k, 0), Low);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), High);
+    }
+
+    #[test]
+    fn test_t_flip_flop() {
+        let mut sim = Simulator::new();
 
-        let gate_index = sim.insert_component(Component::AndGate);
-        let output_index = sim.insert_component(Component::Output(Low));
+        let t = sim.insert_component(Component::Input(Low.into()));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let flip_flop = sim.insert_component(Component::TFlipFlop(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
 
-        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
-        sim.connect(RegisteredPin(in2, 0), RegisteredPin(gate_index, 1));
-        sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(output_index, 0));
+        sim.connect(RegisteredPin(t, 0), RegisteredPin(flip_flop, 0));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(flip_flop, 1));
+        sim.connect(RegisteredPin(flip_flop, 2), RegisteredPin(q, 0));
+        sim.initialize();
+
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        sim.set_input(RegisteredPin(t, 0), High);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(t, 0), Low);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+    }
+
+    #[test]
+    fn test_sr_latch_component() {
+        let mut sim = Simulator::new();
+
+        let set = sim.insert_component(Component::Input(Low.into()));
+        let reset = sim.insert_component(Component::Input(Low.into()));
+        let latch = sim.insert_component(Component::SrLatch(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
+        let not_q = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(set, 0), RegisteredPin(latch, 0));
+        sim.connect(RegisteredPin(reset, 0), RegisteredPin(latch, 1));
+        sim.connect(RegisteredPin(latch, 2), RegisteredPin(q, 0));
+        sim.connect(RegisteredPin(latch, 3), RegisteredPin(not_q, 0));
+        sim.initialize();
+
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
 
+        sim.set_input(RegisteredPin(set, 0), High);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), Low);
+
+        sim.set_input(RegisteredPin(set, 0), Low);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(reset, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), High);
+
+        sim.set_input(RegisteredPin(set, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Invalid);
+        assert_eq!(sim.inspect_pin(RegisteredPin(not_q, 0)), Invalid);
+    }
+
+    #[test]
+    fn test_d_latch() {
+        let mut sim = Simulator::new();
+
+        let d = sim.insert_component(Component::Input(Low.into()));
+        let enable = sim.insert_component(Component::Input(Low.into()));
+        let latch = sim.insert_component(Component::DLatch(Storage::new(1)));
+        let q = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(d, 0), RegisteredPin(latch, 0));
+        sim.connect(RegisteredPin(enable, 0), RegisteredPin(latch, 1));
+        sim.connect(RegisteredPin(latch, 2), RegisteredPin(q, 0));
+        sim.initialize();
+
+        sim.set_input(RegisteredPin(d, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        // Transparent while enabled
+        sim.set_input(RegisteredPin(enable, 0), High);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+
+        sim.set_input(RegisteredPin(d, 0), Low);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), Low);
+
+        sim.set_input(RegisteredPin(d, 0), High);
+        sim.tick();
+        sim.set_input(RegisteredPin(enable, 0), Low);
+        sim.tick();
+        sim.set_input(RegisteredPin(d, 0), Low);
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)), High);
+    }
+
+    #[test]
+    fn test_register() {
+        let mut sim = Simulator::new();
+
+        let d = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let enable = sim.insert_component(Component::Input(Low.into()));
+        let reset = sim.insert_component(Component::Input(Low.into()));
+        let register = sim.insert_component(Component::Register(Storage::new(4)));
+        let q = sim.insert_component(Component::Output(StateVec::new(4, Low)));
+
+        sim.connect(RegisteredPin(d, 0), RegisteredPin(register, 0));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(register, 1));
+        sim.connect(RegisteredPin(enable, 0), RegisteredPin(register, 2));
+        sim.connect(RegisteredPin(reset, 0), RegisteredPin(register, 3));
+        sim.connect(RegisteredPin(register, 4), RegisteredPin(q, 0));
+        sim.initialize();
+
+        sim.set_input(
+            RegisteredPin(d, 0),
+            StateVec::from(vec![Low, High, Low, High]),
         );
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "0000");
+
+        sim.set_input(RegisteredPin(enable, 0), High);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "1010");
+
+        // Holds its value while disabled
+        sim.set_input(RegisteredPin(enable, 0), Low);
+        sim.set_input(
+            RegisteredPin(d, 0),
+            StateVec::from(vec![Low, High, High, Low]),
+        );
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "1010");
 
-        sim.set_input(RegisteredPin(in1, 0), Low);
-        sim.set_input(RegisteredPin(in2, 0), High);
+        // Reset doesn't wait for the clock
+        sim.set_input(RegisteredPin(reset, 0), High);
         sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "0000");
+
+        sim.set_input(RegisteredPin(enable, 0), High);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "0000");
+
+        sim.set_input(RegisteredPin(reset, 0), Low);
+        pulse(&mut sim, clock);
+        assert_eq!(sim.inspect_pin(RegisteredPin(q, 0)).to_string(), "0110");
+    }
+
+    #[test]
+    fn test_shift_register() {
+        let mut sim = Simulator::new();
+
+        let data = sim.insert_component(Component::Input(Low.into()));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let stage1 = sim.insert_component(Component::DFlipFlop(Storage::new(1)));
+        let stage2 = sim.insert_component(Component::DFlipFlop(Storage::new(1)));
+        let output1 = sim.insert_component(Component::Output(Low.into()));
+        let output2 = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(data, 0), RegisteredPin(stage1, 0));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(stage1, 1));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(stage2, 1));
+        sim.connect(RegisteredPin(stage1, 2), RegisteredPin(stage2, 0));
+        sim.connect(RegisteredPin(stage1, 2), RegisteredPin(output1, 0));
+        sim.connect(RegisteredPin(stage2, 2), RegisteredPin(output2, 0));
+        assert_eq!(sim.settle(), Ok(()));
+
+        // The second stage samples what the first one held before the edge, so the bit moves one stage per edge
+        sim.set_input(RegisteredPin(data, 0), High);
+        sim.set_input(RegisteredPin(clock, 0), High);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.inspect_pin(RegisteredPin(output1, 0)), High);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output2, 0)), Low);
+
+        sim.set_input(RegisteredPin(data, 0), Low);
+        sim.set_input(RegisteredPin(clock, 0), Low);
+        assert_eq!(sim.settle(), Ok(()));
+        sim.set_input(RegisteredPin(clock, 0), High);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.inspect_pin(RegisteredPin(output1, 0)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output2, 0)), High);
+    }
+
+    #[test]
+    fn test_rom() {
+        let mut sim = Simulator::new();
+
+        let mut memory = Memory::new(2, 8);
+        memory.load_hex("12 34 56 78").unwrap();
+        let address = sim.insert_component(Component::Input(StateVec::new(2, Low)));
+        let rom = sim.insert_component(Component::Rom(memory));
+        let data = sim.insert_component(Component::Output(StateVec::new(8, Low)));
+        assert_eq!(sim.pin_width(RegisteredPin(rom, 1)), 8);
+
+        sim.connect(RegisteredPin(address, 0), RegisteredPin(rom, 0));
+        sim.connect(RegisteredPin(rom, 1), RegisteredPin(data, 0));
+
+        sim.initialize();
+
+        for (index, word) in [0x12, 0x34, 0x56, 0x78].into_iter().enumerate() {
+            sim.set_input(
+                RegisteredPin(address, 0),
+                StateVec::from_number(index as u64, 2),
+            );
+            sim.tick();
+            assert_eq!(
+                sim.inspect_pin(RegisteredPin(data, 0)).to_number(),
+                Some(word)
+            );
+        }
+
+        // Edits reach the output after the usual delay
+        let delay = sim.delay(ComponentHandle(rom));
+        sim.edit_memory(ComponentHandle(rom), |memory| memory.set(3, 0x9a));
+        sim.advance(delay).unwrap();
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
+            sim.inspect_pin(RegisteredPin(data, 0)).to_number(),
+            Some(0x9a)
         );
 
-        sim.set_input(RegisteredPin(in1, 0), High);
-        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.set_input(
+            RegisteredPin(address, 0),
+            StateVec::from(vec![Low, Invalid]),
+        );
         sim.tick();
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
+            sim.inspect_pin(RegisteredPin(data, 0)).to_string(),
+            "XXXXXXXX"
         );
+    }
 
-        sim.set_input(RegisteredPin(in1, 0), High);
-        sim.set_input(RegisteredPin(in2, 0), High);
+    #[test]
+    fn test_ram() {
+        let mut sim = Simulator::new();
+
+        let address = sim.insert_component(Component::Input(StateVec::new(2, Low)));
+        let data_in = sim.insert_component(Component::Input(StateVec::new(4, Low)));
+        let write_enable = sim.insert_component(Component::Input(Low.into()));
+        let clock = sim.insert_component(Component::Input(Low.into()));
+        let ram = sim.insert_component(Component::Ram(Memory::new(2, 4)));
+        let data_out = sim.insert_component(Component::Output(StateVec::new(4, Low)));
+
+        sim.connect(RegisteredPin(address, 0), RegisteredPin(ram, 0));
+        sim.connect(RegisteredPin(data_in, 0), RegisteredPin(ram, 1));
+        sim.connect(RegisteredPin(write_enable, 0), RegisteredPin(ram, 2));
+        sim.connect(RegisteredPin(clock, 0), RegisteredPin(ram, 3));
+        sim.connect(RegisteredPin(ram, 4), RegisteredPin(data_out, 0));
+
+        sim.initialize();
+        let read = |sim: &Simulator| sim.inspect_pin(RegisteredPin(data_out, 0)).to_number();
+
+        sim.set_input(RegisteredPin(address, 0), StateVec::from_number(2, 2));
+        sim.set_input(RegisteredPin(data_in, 0), StateVec::from_number(9, 4));
+        pulse(&mut sim, clock);
+        assert_eq!(read(&sim), Some(0));
+
+        // Writes wait for the clock
+        sim.set_input(RegisteredPin(write_enable, 0), High);
+        sim.tick();
+        assert_eq!(read(&sim), Some(0));
+        pulse(&mut sim, clock);
+        assert_eq!(read(&sim), Some(9));
+
+        // Reads don't
+        sim.set_input(RegisteredPin(write_enable, 0), Low);
+        sim.set_input(RegisteredPin(address, 0), StateVec::from_number(1, 2));
+        sim.tick();
+        assert_eq!(read(&sim), Some(0));
+        sim.set_input(RegisteredPin(address, 0), StateVec::from_number(2, 2));
         sim.tick();
+        assert_eq!(read(&sim), Some(9));
+
+        // Invalid data is dropped
+        sim.set_input(RegisteredPin(write_enable, 0), High);
+        sim.set_input(
+            RegisteredPin(data_in, 0),
+            StateVec::from(vec![Invalid, Low, Low, Low]),
+        );
+        pulse(&mut sim, clock);
+        assert_eq!(read(&sim), Some(9));
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
+            sim.with_memory(ComponentHandle(ram), |memory| memory.words().to_vec()),
+            Some(vec![0, 0, 9, 0])
         );
     }
 
     #[test]
-    fn test_xor() {
+    fn test_switches() {
         let mut sim = Simulator::new();
 
-        let in1 = sim.insert_component(Component::Input(Low));
-        let in2 = sim.insert_component(Component::Input(Low));
+        let button = sim.insert_component(Component::PushButton(Low));
+        let switch = sim.insert_component(Component::ToggleSwitch(Low));
+        let dip = sim.insert_component(Component::DipSwitch(StateVec::new(4, Low)));
+        let constant = sim.insert_component(Component::Constant(High));
+        let outputs = [button, switch, dip, constant].map(|source| {
+            let width = sim.pin_width(RegisteredPin(source, 0));
+            let output = sim.insert_component(Component::Output(StateVec::new(width, Low)));
+            sim.connect(RegisteredPin(source, 0), RegisteredPin(output, 0));
+            output
+        });
+        let read =
+            |sim: &Simulator, index: usize| sim.inspect_pin(RegisteredPin(outputs[index], 0));
 
-        let gate_index = sim.insert_component(Component::XorGate);
-        let output_index = sim.insert_component(Component::Output(Low));
+        sim.tick();
+        assert_eq!(read(&sim, 3), High);
 
-        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
-        sim.connect(RegisteredPin(in2, 0), RegisteredPin(gate_index, 1));
-        sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(output_index, 0));
+        assert!(sim.press_button(ComponentHandle(button), true));
+        sim.tick();
+        assert_eq!(read(&sim, 0), High);
+        assert!(sim.press_button(ComponentHandle(button), false));
+        sim.tick();
+        assert_eq!(read(&sim, 0), Low);
 
+        assert!(sim.flip_switch(ComponentHandle(switch), 0));
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
-        );
+        assert_eq!(read(&sim, 1), High);
 
-        sim.set_input(RegisteredPin(in1, 0), Low);
-        sim.set_input(RegisteredPin(in2, 0), High);
+        assert!(sim.flip_switch(ComponentHandle(dip), 1));
+        assert!(sim.flip_switch(ComponentHandle(dip), 3));
+        assert!(!sim.flip_switch(ComponentHandle(dip), 4));
         sim.tick();
+        assert_eq!(read(&sim, 2).to_string(), "1010");
+
+        // Only their own kind of interaction works on each
+        assert!(!sim.press_button(ComponentHandle(switch), true));
+        assert!(!sim.flip_switch(ComponentHandle(button), 0));
+        assert!(!sim.flip_switch(ComponentHandle(constant), 0));
+    }
+
+    #[test]
+    fn test_displays() {
+        let mut sim = Simulator::new();
+
+        let led_input = sim.insert_component(Component::Input(High.into()));
+        let led = sim.insert_component(Component::Led(Low));
+        sim.connect(RegisteredPin(led_input, 0), RegisteredPin(led, 0));
+
+        // Only segment g, the last input, is lit
+        let segments = sim.insert_component(Component::SevenSegment(StateVec::new(7, Low)));
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
+            sim.get_component(&ComponentHandle(segments))
+                .borrow()
+                .input_len(),
+            7
         );
+        for pin in 0..7 {
+            let value = if pin == 6 { High } else { Low };
+            let input = sim.insert_component(Component::Input(value.into()));
+            sim.connect(RegisteredPin(input, 0), RegisteredPin(segments, pin));
+        }
 
-        sim.set_input(RegisteredPin(in1, 0), High);
-        sim.set_input(RegisteredPin(in2, 0), Low);
-        sim.tick();
+        let hex_input = sim.insert_component(Component::Input(StateVec::from_number(0xc, 4)));
+        let hex = sim.insert_component(Component::HexDisplay(StateVec::new(4, Low)));
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
+            sim.get_component(&ComponentHandle(hex))
+                .borrow()
+                .output_len(),
+            0
         );
+        sim.connect(RegisteredPin(hex_input, 0), RegisteredPin(hex, 0));
+
+        sim.initialize();
+
+        assert!(matches!(
+            *sim.get_component(&ComponentHandle(led)).borrow(),
+            Component::Led(High)
+        ));
+        match &*sim.get_component(&ComponentHandle(segments)).borrow() {
+            Component::SevenSegment(value) => assert_eq!(value.to_string(), "1000000"),
+            _ => unreachable!(),
+        }
+        match &*sim.get_component(&ComponentHandle(hex)).borrow() {
+            Component::HexDisplay(value) => assert_eq!(value.to_number(), Some(0xc)),
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_propagation_delay() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let not1 = sim.insert_component(Component::NotGate);
+        let not2 = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.set_delay(ComponentHandle(not2), 3);
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(not1, 0));
+        sim.connect(RegisteredPin(not1, 1), RegisteredPin(not2, 0));
+        sim.connect(RegisteredPin(not2, 1), RegisteredPin(output_index, 0));
+
+        assert_eq!(sim.run_until_quiescent(), Ok(4));
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+
+        sim.run_until(5).unwrap();
+        assert_eq!(sim.get_pin(RegisteredPin(not1, 1)), Low);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        assert_eq!(sim.run_until_quiescent(), Ok(8));
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), High);
+    }
+
+    #[test]
+    fn test_tick_keeps_pending_events() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+        let not_index = sim.insert_component(Component::NotGate);
+        let output1 = sim.insert_component(Component::Output(Low.into()));
+        let output2 = sim.insert_component(Component::Output(Low.into()));
+
+        sim.set_delay(ComponentHandle(not_index), 5);
 
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(not_index, 0));
+        sim.connect(RegisteredPin(not_index, 1), RegisteredPin(output1, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(output2, 0));
+
+        sim.initialize();
+        let start = sim.time();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output1, 0)), High);
+
+        // The inverter's new output is still on its way when the other input changes
         sim.set_input(RegisteredPin(in1, 0), High);
+        sim.run_until(start + 2).unwrap();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output1, 0)), High);
+
         sim.set_input(RegisteredPin(in2, 0), High);
         sim.tick();
-        assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low
-        );
+        assert_eq!(sim.inspect_pin(RegisteredPin(output2, 0)), High);
+        assert_eq!(sim.inspect_pin(RegisteredPin(output1, 0)), Low);
+        assert_eq!(sim.time(), start + 5);
     }
 
     #[test]
-    fn test_not() {
+    fn test_hazard_glitch() {
         let mut sim = Simulator::new();
 
-        let in1 = sim.insert_component(Component::Input(Low));
-        let gate_index = sim.insert_component(Component::NotGate);
-        let output_index = sim.insert_component(Component::Output(Low));
+        // a AND (NOT a) is always low once settled, but the inverter delay lets a pulse through
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+        let not_index = sim.insert_component(Component::NotGate);
+        let gate_index = sim.insert_component(Component::AndGate(2));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.set_delay(ComponentHandle(not_index), 2);
 
         sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(not_index, 0));
+        sim.connect(RegisteredPin(not_index, 1), RegisteredPin(gate_index, 1));
         sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(output_index, 0));
 
         sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(in2, 0), High);
+
+        let mut seen = Vec::new();
+        while let Some(time) = sim.step() {
+            seen.push((time, sim.inspect_pin(RegisteredPin(output_index, 0))));
+        }
+
+        assert!(seen.contains(&(sim.time() - 2, High.into())));
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+    }
+
+    #[test]
+    fn test_sr_latch() {
+        let mut sim = Simulator::new();
+
+        // Cross coupled NOR gates, built from or + not
+        let set = sim.insert_component(Component::Input(High.into()));
+        let reset = sim.insert_component(Component::Input(Low.into()));
+
+        let or1 = sim.insert_component(Component::OrGate(2));
+        let q = sim.insert_component(Component::NotGate);
+        let or2 = sim.insert_component(Component::OrGate(2));
+        let q_bar = sim.insert_component(Component::NotGate);
+
+        sim.connect(RegisteredPin(reset, 0), RegisteredPin(or1, 0));
+        sim.connect(RegisteredPin(q_bar, 1), RegisteredPin(or1, 1));
+        sim.connect(RegisteredPin(or1, 2), RegisteredPin(q, 0));
+
+        sim.connect(RegisteredPin(set, 0), RegisteredPin(or2, 0));
+        sim.connect(RegisteredPin(q, 1), RegisteredPin(or2, 1));
+        sim.connect(RegisteredPin(or2, 2), RegisteredPin(q_bar, 0));
+
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(q, 1)), High);
+        assert_eq!(sim.get_pin(RegisteredPin(q_bar, 1)), Low);
+
+        sim.set_input(RegisteredPin(set, 0), Low);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(q, 1)), High);
+
+        sim.set_input(RegisteredPin(reset, 0), High);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(q, 1)), Low);
+        assert_eq!(sim.get_pin(RegisteredPin(q_bar, 1)), High);
+
+        sim.set_input(RegisteredPin(reset, 0), Low);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(q, 1)), Low);
+    }
+
+    #[test]
+    fn test_oscillation() {
+        let mut sim = Simulator::new();
+
+        // Gated ring oscillator: and gate feeding back into itself through an inverter
+        let enable = sim.insert_component(Component::Input(Low.into()));
+        let gate_index = sim.insert_component(Component::AndGate(2));
+        let not_index = sim.insert_component(Component::NotGate);
+
+        sim.connect(RegisteredPin(enable, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 2), RegisteredPin(not_index, 0));
+        sim.connect(RegisteredPin(not_index, 1), RegisteredPin(gate_index, 1));
+
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(gate_index, 2)), Low);
+
+        sim.set_input(RegisteredPin(enable, 0), High);
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            High
+            sim.settle(),
+            Err(SimError::Oscillation {
+                components: vec![ComponentHandle(gate_index), ComponentHandle(not_index)]
+            })
         );
+        assert_eq!(sim.get_pin(RegisteredPin(gate_index, 2)), Invalid);
 
-        sim.set_input(RegisteredPin(in1, 0), High);
+        sim.set_input(RegisteredPin(enable, 0), Low);
+        assert_eq!(sim.settle(), Ok(()));
+        assert_eq!(sim.get_pin(RegisteredPin(gate_index, 2)), Low);
+
+        // With delays the loop keeps toggling forever
+        sim.set_input(RegisteredPin(enable, 0), High);
+        assert!(matches!(
+            sim.run_until_quiescent(),
+            Err(SimError::Unsettled { .. })
+        ));
+    }
+
+    #[test]
+    fn test_custom() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(Low.into()));
+
+        let custom_index = sim.insert_component(Component::Custom(Box::new(nand_gate())));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        {
+            let component = sim.get_component(&ComponentHandle(custom_index)).borrow();
+            assert_eq!(component.input_len(), 2);
+            assert_eq!(component.output_len(), 1);
+            assert_eq!(component.get_label(), "nand");
+        }
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(custom_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(custom_index, 1));
+        sim.connect(
+            RegisteredPin(custom_index, 2),
+            RegisteredPin(output_index, 0),
+        );
+
+        for (a, b, y) in [
+            (Low, Low, High),
+            (Low, High, High),
+            (High, Low, High),
+            (High, High, Low),
+        ] {
+            sim.set_input(RegisteredPin(in1, 0), a);
+            sim.set_input(RegisteredPin(in2, 0), b);
+            sim.tick();
+            assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), y);
+        }
+    }
+
+    #[test]
+    fn test_custom_instances() {
+        let mut sim = Simulator::new();
+
+        // Two instances of the same subcircuit must not share state
+        let nand = nand_gate();
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let in2 = sim.insert_component(Component::Input(High.into()));
+        let first = sim.insert_component(Component::Custom(Box::new(nand.clone())));
+        let second = sim.insert_component(Component::Custom(Box::new(nand)));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(first, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(first, 1));
+        sim.connect(RegisteredPin(first, 2), RegisteredPin(second, 0));
+
+        sim.tick();
+        assert_eq!(sim.get_pin(RegisteredPin(first, 2)), Low);
+        assert_eq!(sim.get_pin(RegisteredPin(second, 2)), High);
+    }
+
+    #[test]
+    fn test_bus() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(Low.into()));
+        let in2 = sim.insert_component(Component::Input(High.into()));
+        let in3 = sim.insert_component(Component::Input(Low.into()));
+
+        let merger = sim.insert_component(Component::Merger(3));
+        let bus_output = sim.insert_component(Component::Output(StateVec::new(3, Low)));
+        let splitter = sim.insert_component(Component::Splitter(3));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        assert_eq!(sim.pin_width(RegisteredPin(merger, 2)), 1);
+        assert_eq!(sim.pin_width(RegisteredPin(merger, 3)), 3);
+        assert_eq!(sim.pin_width(RegisteredPin(splitter, 0)), 3);
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(merger, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(merger, 1));
+        sim.connect(RegisteredPin(in3, 0), RegisteredPin(merger, 2));
+        sim.connect(RegisteredPin(merger, 3), RegisteredPin(splitter, 0));
+        sim.connect(RegisteredPin(splitter, 2), RegisteredPin(output_index, 0));
+
+        sim.tick();
+        assert_eq!(sim.get_pin(RegisteredPin(merger, 3)).to_string(), "010");
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), High);
+
+        sim.set_input(RegisteredPin(in2, 0), Low);
+        sim.set_input(RegisteredPin(in3, 0), High);
+        sim.tick();
+        assert_eq!(sim.get_pin(RegisteredPin(merger, 3)).to_string(), "100");
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Low);
+
+        // A bus can't drive a single bit pin
+        sim.disconnect(RegisteredPin(splitter, 2), RegisteredPin(output_index, 0));
+        sim.connect(RegisteredPin(merger, 3), RegisteredPin(output_index, 0));
+        sim.tick();
+        assert_eq!(sim.inspect_pin(RegisteredPin(output_index, 0)), Invalid);
+
+        sim.connect(RegisteredPin(merger, 3), RegisteredPin(bus_output, 0));
         sim.tick();
         assert_eq!(
-            sim.inspect_pin(RegisteredPin(sim.output_components[0], 0)),
-            Low,
+            sim.inspect_pin(RegisteredPin(bus_output, 0)),
+            StateVec::from(vec![Low, Low, High])
         );
     }
 }
@@ -0,0 +1,245 @@
+//! Contents of ROM and RAM components, and loading them from files. Hex files hold hexadecimal words separated by
+//! whitespace, like Verilog's `$readmemh`: `#` starts a comment that runs to the end of the line and `@` followed
+//! by a hexadecimal address continues at that address. Files ending in `.bin` are raw images where every word takes
+//! as many bytes as it needs, least significant byte first.
+
+use std::{fmt::Display, path::Path};
+
+use crate::{rising_edge, State, StateVec};
+
+#[derive(Debug)]
+pub enum MemoryError {
+    Io(std::io::Error),
+    /// A word or address in a hex file isn't a hexadecimal number that fits
+    InvalidWord {
+        line: usize,
+        word: String,
+    },
+    /// The file holds more words than there are addresses
+    TooLarge {
+        words: usize,
+    },
+}
+
+impl Display for MemoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryError::Io(err) => write!(f, "{err}"),
+            MemoryError::InvalidWord { line, word } => {
+                write!(f, "line {line}: '{word}' isn't a valid word")
+            }
+            MemoryError::TooLarge { words } => {
+                write!(f, "contents don't fit, memory only has {words} words")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+impl From<std::io::Error> for MemoryError {
+    fn from(value: std::io::Error) -> Self {
+        MemoryError::Io(value)
+    }
+}
+
+/// Words of a memory with `2^address` addresses, each `width` bits wide
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    pub address: usize,
+    pub width: usize,
+    words: Vec<u64>,
+    /// Clock level at the last write, used by RAM to find rising edges
+    clock: State,
+}
+
+impl Memory {
+    /// Filled with zeros
+    pub fn new(address: usize, width: usize) -> Memory {
+        Memory {
+            address,
+            width,
+            words: vec![0; 1 << address],
+            clock: State::Invalid,
+        }
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Largest value a word can hold
+    pub fn mask(&self) -> u64 {
+        if self.width >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+
+    /// Stores a word, dropping the bits that don't fit
+    pub fn set(&mut self, address: usize, value: u64) {
+        let mask = self.mask();
+        if let Some(word) = self.words.get_mut(address) {
+            *word = value & mask;
+        }
+    }
+
+    /// Word at an address bus. Addresses with invalid or floating bits read as invalid.
+    pub(crate) fn read(&self, address: &StateVec) -> StateVec {
+        match address.to_number() {
+            Some(address) => StateVec::from_number(self.words[address as usize], self.width),
+            None => StateVec::new(self.width, State::Invalid),
+        }
+    }
+
+    /// Stores `data` at `address` on the rising edge of the clock while write is enabled. Writes to an invalid
+    /// address or of invalid data are dropped, since there's no way to store them.
+    pub(crate) fn write(
+        &mut self,
+        address: &StateVec,
+        data: &StateVec,
+        enable: State,
+        clock: State,
+    ) {
+        if !rising_edge(&mut self.clock, clock) || enable != State::High {
+            return;
+        }
+
+        if let (Some(address), Some(data)) = (address.to_number(), data.to_number()) {
+            self.set(address as usize, data);
+        }
+    }
+
+    /// Replaces the contents with a file, see the module docs for the formats
+    pub fn load(&mut self, path: &Path) -> Result<(), MemoryError> {
+        if path.extension().is_some_and(|extension| extension == "bin") {
+            self.load_binary(&std::fs::read(path)?)
+        } else {
+            self.load_hex(&std::fs::read_to_string(path)?)
+        }
+    }
+
+    /// Replaces the contents with hexadecimal words. Addresses that aren't mentioned are zero.
+    pub fn load_hex(&mut self, source: &str) -> Result<(), MemoryError> {
+        let mut words = vec![0; self.words.len()];
+        let mut address = 0;
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+
+            for word in line.split_whitespace() {
+                let invalid = || MemoryError::InvalidWord {
+                    line: index + 1,
+                    word: word.to_string(),
+                };
+
+                if let Some(target) = word.strip_prefix('@') {
+                    address = usize::from_str_radix(target, 16).map_err(|_| invalid())?;
+                    continue;
+                }
+
+                let value = u64::from_str_radix(word, 16).map_err(|_| invalid())?;
+                if value > self.mask() {
+                    return Err(invalid());
+                }
+
+                *words.get_mut(address).ok_or(MemoryError::TooLarge {
+                    words: self.words.len(),
+                })? = value;
+                address += 1;
+            }
+        }
+
+        self.words = words;
+        Ok(())
+    }
+
+    /// Replaces the contents with a raw image. Addresses past the end of the image are zero.
+    pub fn load_binary(&mut self, bytes: &[u8]) -> Result<(), MemoryError> {
+        let size = self.width.div_ceil(8);
+        if bytes.len().div_ceil(size) > self.words.len() {
+            return Err(MemoryError::TooLarge {
+                words: self.words.len(),
+            });
+        }
+
+        let mask = self.mask();
+        let mut words = vec![0; self.words.len()];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks(size)) {
+            let value = chunk
+                .iter()
+                .rev()
+                .fold(0, |value, byte| value << 8 | *byte as u64);
+            *word = value & mask;
+        }
+
+        self.words = words;
+        Ok(())
+    }
+
+    /// Contents in the hex format, leaving out the zeros at the end
+    pub fn to_hex(&self) -> String {
+        let used = self
+            .words
+            .iter()
+            .rposition(|word| *word != 0)
+            .map_or(0, |last| last + 1);
+
+        self.words[..used]
+            .iter()
+            .map(|word| format!("{word:x}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_hex() {
+        let mut memory = Memory::new(4, 8);
+
+        memory
+            .load_hex("# boot code\n1f 2 # two words\n@8\nFF\n")
+            .unwrap();
+        assert_eq!(&memory.words()[..3], &[0x1f, 0x2, 0]);
+        assert_eq!(memory.words()[8], 0xff);
+        assert_eq!(memory.to_hex(), "1f 2 0 0 0 0 0 0 ff");
+
+        let mut copy = Memory::new(4, 8);
+        copy.load_hex(&memory.to_hex()).unwrap();
+        assert_eq!(copy, memory);
+    }
+
+    #[test]
+    fn test_load_hex_errors() {
+        let mut memory = Memory::new(1, 4);
+
+        assert_eq!(
+            memory.load_hex("1\n2 g").unwrap_err().to_string(),
+            "line 2: 'g' isn't a valid word"
+        );
+        // Doesn't fit in 4 bits
+        assert!(memory.load_hex("10").is_err());
+        assert!(matches!(
+            memory.load_hex("1 2 3"),
+            Err(MemoryError::TooLarge { words: 2 })
+        ));
+
+        // Failed loads leave the contents alone
+        assert_eq!(memory.words(), &[0, 0]);
+    }
+
+    #[test]
+    fn test_load_binary() {
+        let mut memory = Memory::new(2, 12);
+
+        memory.load_binary(&[0x34, 0x12, 0xff, 0xff, 0x01]).unwrap();
+        assert_eq!(memory.words(), &[0x234, 0xfff, 0x1, 0]);
+
+        assert!(memory.load_binary(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Components offered in the side panel for placing on the canvas
+
+use crate::{memory::Memory, nand_gate, Clock, Component, State::*, StateVec, Storage};
+
+/// How a palette entry creates a fresh instance every time it's placed
+pub enum Build {
+    Component(fn() -> Component),
+    /// Gates that can have several inputs get the number picked in the side panel
+    Gate(fn(usize) -> Component),
+}
+
+/// A placeable component
+pub struct PaletteEntry {
+    pub name: &'static str,
+    pub build: Build,
+}
+
+impl PaletteEntry {
+    pub fn build(&self, gate_inputs: usize) -> Component {
+        match self.build {
+            Build::Component(build) => build(),
+            Build::Gate(build) => build(gate_inputs),
+        }
+    }
+}
+
+pub const ENTRIES: &[PaletteEntry] = &[
+    PaletteEntry {
+        name: "Input",
+        build: Build::Component(|| Component::Input(Low.into())),
+    },
+    PaletteEntry {
+        name: "Output",
+        build: Build::Component(|| Component::Output(Low.into())),
+    },
+    PaletteEntry {
+        name: "Push button",
+        build: Build::Component(|| Component::PushButton(Low)),
+    },
+    PaletteEntry {
+        name: "Toggle switch",
+        build: Build::Component(|| Component::ToggleSwitch(Low)),
+    },
+    PaletteEntry {
+        name: "DIP switch (8 way)",
+        build: Build::Component(|| Component::DipSwitch(StateVec::new(8, Low))),
+    },
+    PaletteEntry {
+        name: "Constant high",
+        build: Build::Component(|| Component::Constant(High)),
+    },
+    PaletteEntry {
+        name: "Constant low",
+        build: Build::Component(|| Component::Constant(Low)),
+    },
+    PaletteEntry {
+        name: "Clock",
+        build: Build::Component(|| Component::Clock(Clock::new(20, 0, 0.5))),
+    },
+    PaletteEntry {
+        name: "And",
+        build: Build::Gate(Component::AndGate),
+    },
+    PaletteEntry {
+        name: "Or",
+        build: Build::Gate(Component::OrGate),
+    },
+    PaletteEntry {
+        name: "Xor",
+        build: Build::Gate(Component::XorGate),
+    },
+    PaletteEntry {
+        name: "Nand",
+        build: Build::Gate(Component::NandGate),
+    },
+    PaletteEntry {
+        name: "Nor",
+        build: Build::Gate(Component::NorGate),
+    },
+    PaletteEntry {
+        name: "Xnor",
+        build: Build::Gate(Component::XnorGate),
+    },
+    PaletteEntry {
+        name: "Not",
+        build: Build::Component(|| Component::NotGate),
+    },
+    PaletteEntry {
+        name: "Buffer",
+        build: Build::Component(|| Component::BufferGate),
+    },
+    PaletteEntry {
+        name: "Splitter (4 bit)",
+        build: Build::Component(|| Component::Splitter(4)),
+    },
+    PaletteEntry {
+        name: "Merger (4 bit)",
+        build: Build::Component(|| Component::Merger(4)),
+    },
+    PaletteEntry {
+        name: "D flip-flop",
+        build: Build::Component(|| Component::DFlipFlop(Storage::new(1))),
+    },
+    PaletteEntry {
+        name: "JK flip-flop",
+        build: Build::Component(|| Component::JkFlipFlop(Storage::new(1))),
+    },
+    PaletteEntry {
+        name: "T flip-flop",
+        build: Build::Component(|| Component::TFlipFlop(Storage::new(1))),
+    },
+    PaletteEntry {
+        name: "SR latch",
+        build: Build::Component(|| Component::SrLatch(Storage::new(1))),
+    },
+    PaletteEntry {
+        name: "D latch",
+        build: Build::Component(|| Component::DLatch(Storage::new(1))),
+    },
+    PaletteEntry {
+        name: "Register (4 bit)",
+        build: Build::Component(|| Component::Register(Storage::new(4))),
+    },
+    PaletteEntry {
+        name: "Mux (4:1)",
+        build: Build::Component(|| Component::Multiplexer {
+            select: 2,
+            width: 1,
+        }),
+    },
+    PaletteEntry {
+        name: "Demux (1:4)",
+        build: Build::Component(|| Component::Demultiplexer {
+            select: 2,
+            width: 1,
+        }),
+    },
+    PaletteEntry {
+        name: "Decoder (2 bit)",
+        build: Build::Component(|| Component::Decoder(2)),
+    },
+    PaletteEntry {
+        name: "Priority encoder (4:2)",
+        build: Build::Component(|| Component::PriorityEncoder(2)),
+    },
+    PaletteEntry {
+        name: "Adder (4 bit)",
+        build: Build::Component(|| Component::Adder(4)),
+    },
+    PaletteEntry {
+        name: "Subtractor (4 bit)",
+        build: Build::Component(|| Component::Subtractor(4)),
+    },
+    PaletteEntry {
+        name: "Comparator (4 bit)",
+        build: Build::Component(|| Component::Comparator(4)),
+    },
+    PaletteEntry {
+        name: "Multiplier (4 bit)",
+        build: Build::Component(|| Component::Multiplier(4)),
+    },
+    PaletteEntry {
+        name: "ROM (16 x 8 bit)",
+        build: Build::Component(|| Component::Rom(Memory::new(4, 8))),
+    },
+    PaletteEntry {
+        name: "RAM (16 x 8 bit)",
+        build: Build::Component(|| Component::Ram(Memory::new(4, 8))),
+    },
+    PaletteEntry {
+        name: "Tri-state buffer",
+        build: Build::Component(|| Component::TriState(1)),
+    },
+    PaletteEntry {
+        name: "4 bit output",
+        build: Build::Component(|| Component::Output(StateVec::new(4, Low))),
+    },
+    PaletteEntry {
+        name: "LED",
+        build: Build::Component(|| Component::Led(Low)),
+    },
+    PaletteEntry {
+        name: "7-segment display",
+        build: Build::Component(|| Component::SevenSegment(StateVec::new(7, Low))),
+    },
+    PaletteEntry {
+        name: "Hex display",
+        build: Build::Component(|| Component::HexDisplay(StateVec::new(4, Low))),
+    },
+    PaletteEntry {
+        name: "Nand (custom)",
+        build: Build::Component(|| Component::Custom(Box::new(nand_gate()))),
+    },
+];
@@ -0,0 +1,1108 @@
+//! Project files. A project is stored as JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "components": [
+//!     { "id": 0, "kind": { "type": "input", "value": "1" }, "position": [100.0, 100.0], "size": [100.0, 100.0] },
+//!     { "id": 1, "kind": { "type": "not" }, "delay": 3, "position": [300.0, 100.0], "size": [100.0, 100.0] },
+//!     { "id": 2, "kind": { "type": "output", "width": 1 } }
+//!   ],
+//!   "connections": [
+//!     { "from": { "component": 0, "pin": 0 }, "to": { "component": 1, "pin": 0 } },
+//!     { "from": { "component": 1, "pin": 1 }, "to": { "component": 2, "pin": 0 } }
+//!   ],
+//!   "settings": { "wire_style": "orthogonal", "grid_spacing": 20.0 }
+//! }
+//! ```
+//!
+//! - `version` is bumped whenever the format changes. Files from a newer version are rejected.
+//! - `id`s only identify components inside the file, they don't have to be contiguous.
+//! - Component kinds are `input`, `output`, `or`, `and`, `xor`, `nand`, `nor`, `xnor`, `not`, `buffer`, `splitter`,
+//!   `merger`, `tri_state`, `clock`, `d_flip_flop`, `jk_flip_flop`, `t_flip_flop`, `sr_latch`, `d_latch`,
+//!   `register`, `multiplexer`, `demultiplexer`, `decoder`, `priority_encoder`, `adder`, `subtractor`,
+//!   `comparator`, `multiplier`, `rom`, `ram`, `led`, `seven_segment`, `hex_display`, `push_button`,
+//!   `toggle_switch`, `dip_switch`, `constant` and `custom`. `splitter`, `merger`, `tri_state`, `register` and
+//!   `output` take a `width`, 1 to 64, and so do the arithmetic components, which are 1 to 32 bits wide. `input`
+//!   takes its current `value`. `or`, `and`, `xor`, `nand`, `nor` and `xnor` take their number of `inputs`, 2 to
+//!   32, which is 2 if left out. `multiplexer` and `demultiplexer` take a `width`, 1 to 64, and the number of
+//!   `select` bits, 1 to 5, `decoder` and `priority_encoder` just the `select` bits. `toggle_switch`, `dip_switch`
+//!   and `constant` take their `value`, which has a single bit except for DIP switches and can only be `0` or `1`.
+//!   `push_button`s are saved released. `clock` takes a `period`, a `phase` and a `duty_cycle` between 0 and 1.
+//!   `rom` and `ram` take the number of `address` bits, 1 to 16, the `width` of their words, 1 to 32, and their
+//!   `contents` in the hex format of the `memory` module, which are all zeros if left out. Other stored values
+//!   aren't saved, flip-flops, latches and registers start out low.
+//! - Values are written most significant bit first, with `0`, `1`, `X` for invalid bits and `Z` for high
+//!   impedance.
+//! - Pins are numbered like `RegisteredPin`: inputs first, then outputs. A connection goes `from` an output pin
+//!   `to` an input pin of the same width. Pins joined by connections form a net, which only has several drivers
+//!   if they're all `tri_state` buffers.
+//! - `delay` overrides the default propagation delay of the component.
+//! - `position` and `size` place the component in the editor, where the size is rounded up to whole grid steps and
+//!   made tall enough for the pins. Components without a position aren't shown.
+//! - `custom` components embed their netlist under `circuit`, which has its own `components` and `connections`.
+//!   `inputs` and `outputs` list the ports as `{ "name": ..., "component": ... }`, where `component` is the id
+//!   of an `input` or `output` component inside `circuit`.
+//! - `settings` holds editor preferences for the project and can be left out. `wire_style` is `bezier` or
+//!   `orthogonal`. `grid_spacing` is the distance between grid lines, which elements are sized and their pins
+//!   spaced by, so it has to match for the saved wires to line up with the pins.
+
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    element::{Element, DEFAULT_GRID_SPACING},
+    memory::{Memory, MemoryError},
+    routing::WireStyle,
+    Clock, Component, ComponentHandle, ConnectionError, RegisteredPin, SimTime, Simulator, State,
+    StateVec, Storage, Subcircuit,
+};
+
+/// Version written to new files
+pub const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+    DuplicateComponent(usize),
+    /// A connection or port refers to a component id that isn't in the file
+    UnknownComponent(usize),
+    /// A connection refers to a pin the component doesn't have, or uses it in the wrong direction
+    InvalidPin {
+        component: usize,
+        pin: usize,
+        expected: &'static str,
+    },
+    /// An input pin is connected to several outputs that can't all float
+    MultipleDrivers {
+        component: usize,
+        pin: usize,
+    },
+    /// The same two pins are connected twice
+    DuplicateConnection {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    WidthMismatch {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    InvalidValue(String),
+    /// A subcircuit port refers to a component that isn't an input or output
+    InvalidPort {
+        name: String,
+        component: usize,
+    },
+    /// A gate has more or fewer inputs than gates support
+    InvalidInputCount(usize),
+    /// An output, splitter, merger, tri-state buffer, register, multiplexer or demultiplexer has a bus wider or
+    /// narrower than supported
+    InvalidWidth(usize),
+    /// A multiplexer, demultiplexer, decoder or encoder has an unsupported number of select bits
+    InvalidSelectWidth(usize),
+    /// An arithmetic component works on numbers wider or narrower than supported
+    InvalidArithmeticWidth(usize),
+    /// A memory has more address bits or wider words than supported
+    InvalidMemorySize {
+        address: usize,
+        width: usize,
+    },
+    InvalidContents(MemoryError),
+}
+
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectError::Io(err) => write!(f, "{err}"),
+            ProjectError::Parse(err) => write!(f, "malformed project file: {err}"),
+            ProjectError::UnsupportedVersion(version) => write!(
+                f,
+                "project file version {version} is newer than the supported version {VERSION}"
+            ),
+            ProjectError::DuplicateComponent(id) => write!(f, "component id {id} is used twice"),
+            ProjectError::UnknownComponent(id) => {
+                write!(f, "reference to component {id}, which doesn't exist")
+            }
+            ProjectError::InvalidPin {
+                component,
+                pin,
+                expected,
+            } => write!(f, "component {component} has no {expected} pin {pin}"),
+            ProjectError::MultipleDrivers { component, pin } => write!(
+                f,
+                "input pin {pin} of component {component} has more than one driver that isn't tri-state"
+            ),
+            ProjectError::DuplicateConnection { from, to } => write!(
+                f,
+                "component {} pin {} is connected to component {} pin {} more than once",
+                from.0, from.1, to.0, to.1
+            ),
+            ProjectError::WidthMismatch { from, to } => write!(
+                f,
+                "connection from component {} pin {} to component {} pin {} joins pins of different widths",
+                from.0, from.1, to.0, to.1
+            ),
+            ProjectError::InvalidValue(value) => write!(f, "'{value}' isn't a valid value"),
+            ProjectError::InvalidPort { name, component } => write!(
+                f,
+                "port '{name}' refers to component {component}, which isn't an input or output"
+            ),
+            ProjectError::InvalidInputCount(inputs) => write!(
+                f,
+                "gates take between {} and {} inputs, not {inputs}",
+                Component::GATE_INPUTS.start(),
+                Component::GATE_INPUTS.end()
+            ),
+            ProjectError::InvalidWidth(width) => write!(
+                f,
+                "buses are between {} and {} bits wide, not {width}",
+                Component::BUS_BITS.start(),
+                Component::BUS_BITS.end()
+            ),
+            ProjectError::InvalidSelectWidth(select) => write!(
+                f,
+                "select buses are between {} and {} bits wide, not {select}",
+                Component::SELECT_BITS.start(),
+                Component::SELECT_BITS.end()
+            ),
+            ProjectError::InvalidArithmeticWidth(width) => write!(
+                f,
+                "arithmetic components are between {} and {} bits wide, not {width}",
+                Component::ARITHMETIC_BITS.start(),
+                Component::ARITHMETIC_BITS.end()
+            ),
+            ProjectError::InvalidMemorySize { address, width } => write!(
+                f,
+                "memories have {} to {} address bits and words of {} to {} bits, not {address} and {width}",
+                Component::ADDRESS_BITS.start(),
+                Component::ADDRESS_BITS.end(),
+                Component::WORD_BITS.start(),
+                Component::WORD_BITS.end()
+            ),
+            ProjectError::InvalidContents(err) => write!(f, "invalid memory contents: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl From<std::io::Error> for ProjectError {
+    fn from(value: std::io::Error) -> Self {
+        ProjectError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for ProjectError {
+    fn from(value: serde_json::Error) -> Self {
+        ProjectError::Parse(value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectFile {
+    version: u32,
+    #[serde(flatten)]
+    netlist: Netlist,
+    #[serde(default)]
+    settings: Settings,
+}
+
+/// Per project editor preferences
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub wire_style: WireStyle,
+    pub grid_spacing: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            wire_style: WireStyle::default(),
+            grid_spacing: DEFAULT_GRID_SPACING,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Netlist {
+    components: Vec<ComponentEntry>,
+    connections: Vec<ConnectionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ComponentEntry {
+    id: usize,
+    kind: ComponentKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delay: Option<SimTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<(f64, f64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<(f64, f64)>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ComponentKind {
+    Input {
+        value: String,
+    },
+    Output {
+        width: usize,
+    },
+    PushButton,
+    ToggleSwitch {
+        value: String,
+    },
+    DipSwitch {
+        value: String,
+    },
+    Constant {
+        value: String,
+    },
+    Or {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    And {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    Xor {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    Nand {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    Nor {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    Xnor {
+        #[serde(default = "default_gate_inputs")]
+        inputs: usize,
+    },
+    Not,
+    Buffer,
+    Splitter {
+        width: usize,
+    },
+    Merger {
+        width: usize,
+    },
+    TriState {
+        width: usize,
+    },
+    Clock {
+        period: SimTime,
+        phase: SimTime,
+        duty_cycle: f64,
+    },
+    DFlipFlop,
+    JkFlipFlop,
+    TFlipFlop,
+    SrLatch,
+    DLatch,
+    Register {
+        width: usize,
+    },
+    Multiplexer {
+        select: usize,
+        width: usize,
+    },
+    Demultiplexer {
+        select: usize,
+        width: usize,
+    },
+    Decoder {
+        select: usize,
+    },
+    PriorityEncoder {
+        select: usize,
+    },
+    Adder {
+        width: usize,
+    },
+    Subtractor {
+        width: usize,
+    },
+    Comparator {
+        width: usize,
+    },
+    Multiplier {
+        width: usize,
+    },
+    Rom {
+        address: usize,
+        width: usize,
+        #[serde(default)]
+        contents: String,
+    },
+    Ram {
+        address: usize,
+        width: usize,
+        #[serde(default)]
+        contents: String,
+    },
+    Led,
+    SevenSegment,
+    HexDisplay,
+    Custom {
+        name: String,
+        inputs: Vec<Port>,
+        outputs: Vec<Port>,
+        circuit: Netlist,
+    },
+}
+
+fn default_gate_inputs() -> usize {
+    2
+}
+
+#[derive(Serialize, Deserialize)]
+struct Port {
+    name: String,
+    component: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PinRef {
+    component: usize,
+    pin: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConnectionEntry {
+    from: PinRef,
+    to: PinRef,
+}
+
+/// Writes the circuit and the layout of its elements to a file
+pub fn save(
+    path: &Path,
+    sim: &Simulator,
+    elements: &[Element],
+    settings: &Settings,
+) -> Result<(), ProjectError> {
+    std::fs::write(path, to_string(sim, elements, settings)?)?;
+    Ok(())
+}
+
+/// Reads a project file, rebuilding the circuit and its elements
+pub fn load(path: &Path) -> Result<(Simulator, Vec<Element>, Settings), ProjectError> {
+    from_str(&std::fs::read_to_string(path)?)
+}
+
+pub fn to_string(
+    sim: &Simulator,
+    elements: &[Element],
+    settings: &Settings,
+) -> Result<String, ProjectError> {
+    let layout = elements
+        .iter()
+        .map(|element| (element.component.0, element))
+        .collect::<HashMap<_, _>>();
+
+    let file = ProjectFile {
+        version: VERSION,
+        netlist: write_netlist(sim, &layout),
+        settings: *settings,
+    };
+
+    Ok(serde_json::to_string_pretty(&file)?)
+}
+
+pub fn from_str(source: &str) -> Result<(Simulator, Vec<Element>, Settings), ProjectError> {
+    let file: ProjectFile = serde_json::from_str(source)?;
+    if file.version > VERSION {
+        return Err(ProjectError::UnsupportedVersion(file.version));
+    }
+
+    let (sim, ids) = read_netlist(&file.netlist)?;
+
+    let elements = file
+        .netlist
+        .components
+        .iter()
+        .filter_map(|entry| {
+            let position = entry.position?;
+            let element = Element::new(ComponentHandle(ids[&entry.id]), position.into());
+
+            Some(match entry.size {
+                Some(size) => element.with_size(size),
+                None => element,
+            })
+        })
+        .collect();
+
+    Ok((sim, elements, file.settings))
+}
+
+fn write_netlist(sim: &Simulator, layout: &HashMap<usize, &Element>) -> Netlist {
+    let components = sim
+        .components
+        .iter()
+        .enumerate()
+        .filter(|(_, component)| !matches!(*component.borrow(), Component::Removed))
+        .map(|(index, component)| {
+            let element = layout.get(&index);
+
+            ComponentEntry {
+                id: index,
+                kind: component_kind(&component.borrow()),
+                delay: sim.delays.get(&index).copied(),
+                position: element.map(|element| (element.position.x, element.position.y)),
+                size: element.map(|element| (element.size.width, element.size.height)),
+            }
+        })
+        .collect();
+
+    let mut connections = sim
+        .connections()
+        .map(|(from, to)| ConnectionEntry {
+            from: PinRef {
+                component: from.0,
+                pin: from.1,
+            },
+            to: PinRef {
+                component: to.0,
+                pin: to.1,
+            },
+        })
+        .collect::<Vec<_>>();
+    // Keep the output stable so files diff nicely
+    connections.sort_by_key(|connection| (connection.to.component, connection.to.pin));
+
+    Netlist {
+        components,
+        connections,
+    }
+}
+
+fn component_kind(component: &Component) -> ComponentKind {
+    match component {
+        Component::Input(value) => ComponentKind::Input {
+            value: value.to_string(),
+        },
+        Component::Output(value) => ComponentKind::Output {
+            width: value.width(),
+        },
+        Component::PushButton(_) => ComponentKind::PushButton,
+        Component::ToggleSwitch(value) => ComponentKind::ToggleSwitch {
+            value: StateVec::from(*value).to_string(),
+        },
+        Component::DipSwitch(value) => ComponentKind::DipSwitch {
+            value: value.to_string(),
+        },
+        Component::Constant(value) => ComponentKind::Constant {
+            value: StateVec::from(*value).to_string(),
+        },
+        Component::OrGate(inputs) => ComponentKind::Or { inputs: *inputs },
+        Component::AndGate(inputs) => ComponentKind::And { inputs: *inputs },
+        Component::XorGate(inputs) => ComponentKind::Xor { inputs: *inputs },
+        Component::NandGate(inputs) => ComponentKind::Nand { inputs: *inputs },
+        Component::NorGate(inputs) => ComponentKind::Nor { inputs: *inputs },
+        Component::XnorGate(inputs) => ComponentKind::Xnor { inputs: *inputs },
+        Component::NotGate => ComponentKind::Not,
+        Component::BufferGate => ComponentKind::Buffer,
+        Component::Splitter(width) => ComponentKind::Splitter { width: *width },
+        Component::Merger(width) => ComponentKind::Merger { width: *width },
+        Component::TriState(width) => ComponentKind::TriState { width: *width },
+        Component::Clock(clock) => ComponentKind::Clock {
+            period: clock.period,
+            phase: clock.phase,
+            duty_cycle: clock.duty_cycle,
+        },
+        Component::DFlipFlop(_) => ComponentKind::DFlipFlop,
+        Component::JkFlipFlop(_) => ComponentKind::JkFlipFlop,
+        Component::TFlipFlop(_) => ComponentKind::TFlipFlop,
+        Component::SrLatch(_) => ComponentKind::SrLatch,
+        Component::DLatch(_) => ComponentKind::DLatch,
+        Component::Register(storage) => ComponentKind::Register {
+            width: storage.value.width(),
+        },
+        Component::Multiplexer { select, width } => ComponentKind::Multiplexer {
+            select: *select,
+            width: *width,
+        },
+        Component::Demultiplexer { select, width } => ComponentKind::Demultiplexer {
+            select: *select,
+            width: *width,
+        },
+        Component::Decoder(select) => ComponentKind::Decoder { select: *select },
+        Component::PriorityEncoder(select) => ComponentKind::PriorityEncoder { select: *select },
+        Component::Adder(width) => ComponentKind::Adder { width: *width },
+        Component::Subtractor(width) => ComponentKind::Subtractor { width: *width },
+        Component::Comparator(width) => ComponentKind::Comparator { width: *width },
+        Component::Multiplier(width) => ComponentKind::Multiplier { width: *width },
+        Component::Rom(memory) => ComponentKind::Rom {
+            address: memory.address,
+            width: memory.width,
+            contents: memory.to_hex(),
+        },
+        Component::Ram(memory) => ComponentKind::Ram {
+            address: memory.address,
+            width: memory.width,
+            contents: memory.to_hex(),
+        },
+        Component::Led(_) => ComponentKind::Led,
+        Component::SevenSegment(_) => ComponentKind::SevenSegment,
+        Component::HexDisplay(_) => ComponentKind::HexDisplay,
+        Component::Custom(subcircuit) => {
+            let ports = |ports: &[(String, usize)]| {
+                ports
+                    .iter()
+                    .map(|(name, component)| Port {
+                        name: name.clone(),
+                        component: *component,
+                    })
+                    .collect()
+            };
+
+            ComponentKind::Custom {
+                name: subcircuit.name.clone(),
+                inputs: ports(&subcircuit.inputs),
+                outputs: ports(&subcircuit.outputs),
+                circuit: write_netlist(&subcircuit.sim, &HashMap::new()),
+            }
+        }
+        Component::Removed => unreachable!("removed components aren't saved"),
+    }
+}
+
+/// Builds a simulator from a netlist. Also returns the mapping from file ids to component indices.
+fn read_netlist(netlist: &Netlist) -> Result<(Simulator, HashMap<usize, usize>), ProjectError> {
+    let mut sim = Simulator::new();
+    let mut ids = HashMap::new();
+
+    for entry in &netlist.components {
+        if ids.contains_key(&entry.id) {
+            return Err(ProjectError::DuplicateComponent(entry.id));
+        }
+
+        let index = sim.insert_component(read_component(&entry.kind)?);
+        if let Some(delay) = entry.delay {
+            sim.set_delay(ComponentHandle(index), delay);
+        }
+
+        ids.insert(entry.id, index);
+    }
+
+    let lookup = |id: usize| {
+        ids.get(&id)
+            .copied()
+            .ok_or(ProjectError::UnknownComponent(id))
+    };
+
+    for connection in &netlist.connections {
+        let (from, to) = (&connection.from, &connection.to);
+        let driver = RegisteredPin(lookup(from.component)?, from.pin);
+        let sink = RegisteredPin(lookup(to.component)?, to.pin);
+
+        match sim.check_connection(driver, sink) {
+            Ok(()) => (),
+            Err(ConnectionError::NotAnOutput) => {
+                return Err(ProjectError::InvalidPin {
+                    component: from.component,
+                    pin: from.pin,
+                    expected: "output",
+                })
+            }
+            Err(ConnectionError::NotAnInput) => {
+                return Err(ProjectError::InvalidPin {
+                    component: to.component,
+                    pin: to.pin,
+                    expected: "input",
+                })
+            }
+            Err(ConnectionError::AlreadyConnected) => {
+                return Err(ProjectError::DuplicateConnection {
+                    from: (from.component, from.pin),
+                    to: (to.component, to.pin),
+                })
+            }
+            Err(ConnectionError::AlreadyDriven) => {
+                return Err(ProjectError::MultipleDrivers {
+                    component: to.component,
+                    pin: to.pin,
+                })
+            }
+            Err(ConnectionError::WidthMismatch { .. }) => {
+                return Err(ProjectError::WidthMismatch {
+                    from: (from.component, from.pin),
+                    to: (to.component, to.pin),
+                })
+            }
+        }
+
+        sim.connect(driver, sink);
+    }
+
+    Ok((sim, ids))
+}
+
+fn read_component(kind: &ComponentKind) -> Result<Component, ProjectError> {
+    let gate_inputs = |inputs: &usize| {
+        if Component::GATE_INPUTS.contains(inputs) {
+            Ok(*inputs)
+        } else {
+            Err(ProjectError::InvalidInputCount(*inputs))
+        }
+    };
+    let bus_width = |width: &usize| {
+        if Component::BUS_BITS.contains(width) {
+            Ok(*width)
+        } else {
+            Err(ProjectError::InvalidWidth(*width))
+        }
+    };
+    let select_bits = |select: &usize| {
+        if Component::SELECT_BITS.contains(select) {
+            Ok(*select)
+        } else {
+            Err(ProjectError::InvalidSelectWidth(*select))
+        }
+    };
+    let arithmetic_width = |width: &usize| {
+        if Component::ARITHMETIC_BITS.contains(width) {
+            Ok(*width)
+        } else {
+            Err(ProjectError::InvalidArithmeticWidth(*width))
+        }
+    };
+    // Switches and constants are always either low or high
+    let levels = |value: &str| {
+        let levels = decode_value(value)?;
+        if levels
+            .bits()
+            .iter()
+            .all(|bit| matches!(bit, State::Low | State::High))
+        {
+            Ok(levels)
+        } else {
+            Err(ProjectError::InvalidValue(value.to_string()))
+        }
+    };
+    let level = |value: &str| match levels(value)?.bits() {
+        [bit] => Ok(*bit),
+        _ => Err(ProjectError::InvalidValue(value.to_string())),
+    };
+    let memory = |address: &usize, width: &usize, contents: &str| {
+        if !Component::ADDRESS_BITS.contains(address) || !Component::WORD_BITS.contains(width) {
+            return Err(ProjectError::InvalidMemorySize {
+                address: *address,
+                width: *width,
+            });
+        }
+
+        let mut memory = Memory::new(*address, *width);
+        memory
+            .load_hex(contents)
+            .map_err(ProjectError::InvalidContents)?;
+        Ok(memory)
+    };
+
+    Ok(match kind {
+        ComponentKind::Input { value } => Component::Input(decode_value(value)?),
+        ComponentKind::Output { width } => {
+            Component::Output(StateVec::new(bus_width(width)?, State::Low))
+        }
+        ComponentKind::PushButton => Component::PushButton(State::Low),
+        ComponentKind::ToggleSwitch { value } => Component::ToggleSwitch(level(value)?),
+        ComponentKind::DipSwitch { value } => Component::DipSwitch(levels(value)?),
+        ComponentKind::Constant { value } => Component::Constant(level(value)?),
+        ComponentKind::Or { inputs } => Component::OrGate(gate_inputs(inputs)?),
+        ComponentKind::And { inputs } => Component::AndGate(gate_inputs(inputs)?),
+        ComponentKind::Xor { inputs } => Component::XorGate(gate_inputs(inputs)?),
+        ComponentKind::Nand { inputs } => Component::NandGate(gate_inputs(inputs)?),
+        ComponentKind::Nor { inputs } => Component::NorGate(gate_inputs(inputs)?),
+        ComponentKind::Xnor { inputs } => Component::XnorGate(gate_inputs(inputs)?),
+        ComponentKind::Not => Component::NotGate,
+        ComponentKind::Buffer => Component::BufferGate,
+        ComponentKind::Splitter { width } => Component::Splitter(bus_width(width)?),
+        ComponentKind::Merger { width } => Component::Merger(bus_width(width)?),
+        ComponentKind::TriState { width } => Component::TriState(bus_width(width)?),
+        ComponentKind::Clock {
+            period,
+            phase,
+            duty_cycle,
+        } => Component::Clock(Clock::new(*period, *phase, *duty_cycle)),
+        ComponentKind::DFlipFlop => Component::DFlipFlop(Storage::new(1)),
+        ComponentKind::JkFlipFlop => Component::JkFlipFlop(Storage::new(1)),
+        ComponentKind::TFlipFlop => Component::TFlipFlop(Storage::new(1)),
+        ComponentKind::SrLatch => Component::SrLatch(Storage::new(1)),
+        ComponentKind::DLatch => Component::DLatch(Storage::new(1)),
+        ComponentKind::Register { width } => Component::Register(Storage::new(bus_width(width)?)),
+        ComponentKind::Multiplexer { select, width } => Component::Multiplexer {
+            select: select_bits(select)?,
+            width: bus_width(width)?,
+        },
+        ComponentKind::Demultiplexer { select, width } => Component::Demultiplexer {
+            select: select_bits(select)?,
+            width: bus_width(width)?,
+        },
+        ComponentKind::Decoder { select } => Component::Decoder(select_bits(select)?),
+        ComponentKind::PriorityEncoder { select } => {
+            Component::PriorityEncoder(select_bits(select)?)
+        }
+        ComponentKind::Adder { width } => Component::Adder(arithmetic_width(width)?),
+        ComponentKind::Subtractor { width } => Component::Subtractor(arithmetic_width(width)?),
+        ComponentKind::Comparator { width } => Component::Comparator(arithmetic_width(width)?),
+        ComponentKind::Multiplier { width } => Component::Multiplier(arithmetic_width(width)?),
+        ComponentKind::Rom {
+            address,
+            width,
+            contents,
+        } => Component::Rom(memory(address, width, contents)?),
+        ComponentKind::Ram {
+            address,
+            width,
+            contents,
+        } => Component::Ram(memory(address, width, contents)?),
+        ComponentKind::Led => Component::Led(State::Low),
+        ComponentKind::SevenSegment => Component::SevenSegment(StateVec::new(7, State::Low)),
+        ComponentKind::HexDisplay => Component::HexDisplay(StateVec::new(4, State::Low)),
+        ComponentKind::Custom {
+            name,
+            inputs,
+            outputs,
+            circuit,
+        } => {
+            let (sim, ids) = read_netlist(circuit)?;
+
+            let port = |port: &Port, ports: &[usize]| {
+                let component = *ids
+                    .get(&port.component)
+                    .ok_or(ProjectError::UnknownComponent(port.component))?;
+
+                if !ports.contains(&component) {
+                    return Err(ProjectError::InvalidPort {
+                        name: port.name.clone(),
+                        component: port.component,
+                    });
+                }
+
+                Ok((port.name.clone(), component))
+            };
+
+            let inputs = inputs
+                .iter()
+                .map(|p| port(p, &sim.input_components))
+                .collect::<Result<Vec<_>, _>>()?;
+            let outputs = outputs
+                .iter()
+                .map(|p| port(p, &sim.output_components))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let subcircuit = inputs.into_iter().fold(
+                Subcircuit::new(name.clone(), sim),
+                |subcircuit, (name, component)| subcircuit.with_input(name, component),
+            );
+            let subcircuit = outputs
+                .into_iter()
+                .fold(subcircuit, |subcircuit, (name, component)| {
+                    subcircuit.with_output(name, component)
+                });
+
+            Component::Custom(Box::new(subcircuit))
+        }
+    })
+}
+
+fn decode_value(value: &str) -> Result<StateVec, ProjectError> {
+    value
+        .chars()
+        .rev()
+        .map(|c| match c {
+            '0' => Ok(State::Low),
+            '1' => Ok(State::High),
+            'X' | 'x' => Ok(State::Invalid),
+            'Z' | 'z' => Ok(State::HighZ),
+            _ => Err(ProjectError::InvalidValue(value.to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|bits| {
+            if bits.is_empty() {
+                Err(ProjectError::InvalidValue(value.to_string()))
+            } else {
+                Ok(StateVec::from(bits))
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nand_gate;
+    use vello::kurbo::{Point, Size};
+    use State::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let in2 = sim.insert_component(Component::Input(High.into()));
+        let custom_index = sim.insert_component(Component::Custom(Box::new(nand_gate())));
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.set_delay(ComponentHandle(custom_index), 4);
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(custom_index, 0));
+        sim.connect(RegisteredPin(in2, 0), RegisteredPin(custom_index, 1));
+        sim.connect(
+            RegisteredPin(custom_index, 2),
+            RegisteredPin(output_index, 0),
+        );
+
+        let mut elements = vec![
+            Element::new(ComponentHandle(in1), (100.0, 100.0).into()),
+            Element::new(ComponentHandle(custom_index), (300.0, 100.0).into())
+                .with_size((120.0, 80.0)),
+        ];
+
+        let settings = Settings {
+            wire_style: WireStyle::Orthogonal,
+            grid_spacing: 10.0,
+        };
+
+        // Saved like the editor lays them out
+        let mut pins = HashMap::new();
+        for element in &mut elements {
+            element.calculate_positions(&sim, &mut pins, settings.grid_spacing);
+        }
+
+        let source = to_string(&sim, &elements, &settings).unwrap();
+        let (mut loaded, mut loaded_elements, loaded_settings) = from_str(&source).unwrap();
+
+        assert_eq!(loaded.components.len(), 4);
+        let sorted = |sim: &Simulator| {
+            let mut connections = sim.connections().collect::<Vec<_>>();
+            connections.sort();
+            connections
+        };
+        assert_eq!(sorted(&loaded), sorted(&sim));
+        assert_eq!(loaded.delay(ComponentHandle(custom_index)), 4);
+
+        // Laying the loaded elements out gives the same bodies and pins as before saving
+        let mut loaded_pins = HashMap::new();
+        for element in &mut loaded_elements {
+            element.calculate_positions(&loaded, &mut loaded_pins, loaded_settings.grid_spacing);
+        }
+        assert_eq!(loaded_elements.len(), 2);
+        assert_eq!(loaded_elements[1].component, ComponentHandle(custom_index));
+        assert_eq!(loaded_elements[1].position, Point::new(300.0, 100.0));
+        assert_eq!(loaded_elements[1].size, Size::new(120.0, 80.0));
+        assert_eq!(loaded_elements[0].size, elements[0].size);
+        assert_eq!(loaded_pins, pins);
+
+        assert_eq!(loaded_settings, settings);
+
+        // Files saved before the grid spacing was get the default one
+        let (_, _, old_settings) = from_str(
+            r#"{ "version": 1, "components": [], "connections": [], "settings": { "wire_style": "orthogonal" } }"#,
+        )
+        .unwrap();
+        assert_eq!(old_settings.grid_spacing, DEFAULT_GRID_SPACING);
+
+        assert_eq!(
+            to_string(&loaded, &loaded_elements, &loaded_settings).unwrap(),
+            source
+        );
+
+        loaded.initialize();
+        assert_eq!(loaded.inspect_pin(RegisteredPin(output_index, 0)), Low);
+    }
+
+    #[test]
+    fn test_removed_components() {
+        let mut sim = Simulator::new();
+
+        let in1 = sim.insert_component(Component::Input(High.into()));
+        let gate_index = sim.insert_component(Component::NotGate);
+        let output_index = sim.insert_component(Component::Output(Low.into()));
+
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(gate_index, 0));
+        sim.connect(RegisteredPin(gate_index, 1), RegisteredPin(output_index, 0));
+        sim.remove_component(ComponentHandle(gate_index));
+        sim.connect(RegisteredPin(in1, 0), RegisteredPin(output_index, 0));
+
+        let elements = vec![Element::new(
+            ComponentHandle(output_index),
+            (300.0, 100.0).into(),
+        )];
+
+        // Tombstones aren't saved, so the loaded circuit is compacted
+        let source = to_string(&sim, &elements, &Settings::default()).unwrap();
+        let (mut loaded, loaded_elements, _) = from_str(&source).unwrap();
+        assert_eq!(loaded.components.len(), 2);
+        assert_eq!(loaded_elements[0].component, ComponentHandle(1));
+
+        loaded.initialize();
+        assert_eq!(loaded.inspect_pin(RegisteredPin(1, 0)), High);
+    }
+
+    #[test]
+    fn test_values() {
+        let value = decode_value("Z1x0").unwrap();
+
+        assert_eq!(value.bits(), &[Low, Invalid, High, HighZ]);
+        assert_eq!(value.to_string(), "Z1X0");
+        assert!(decode_value("").is_err());
+        assert!(decode_value("2").is_err());
+    }
+
+    #[test]
+    fn test_multiple_drivers() {
+        let with_drivers = |kind: &str| {
+            format!(
+                r#"{{ "version": 1, "components": [
+                    {{ "id": 0, "kind": {kind} }},
+                    {{ "id": 1, "kind": {kind} }},
+                    {{ "id": 2, "kind": {{ "type": "output", "width": 1 }} }}
+                ], "connections": [
+                    {{ "from": {{ "component": 0, "pin": 2 }}, "to": {{ "component": 2, "pin": 0 }} }},
+                    {{ "from": {{ "component": 1, "pin": 2 }}, "to": {{ "component": 2, "pin": 0 }} }}
+                ] }}"#
+            )
+        };
+
+        // Tri-state buffers can share a net
+        let (sim, _, _) =
+            from_str(&with_drivers(r#"{ "type": "tri_state", "width": 1 }"#)).unwrap();
+        assert_eq!(sim.net_of(RegisteredPin(2, 0)).unwrap().drivers().len(), 2);
+
+        assert_eq!(
+            from_str(&with_drivers(r#"{ "type": "and" }"#))
+                .err()
+                .unwrap()
+                .to_string(),
+            "input pin 0 of component 2 has more than one driver that isn't tri-state"
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        let error = |source: &str| from_str(source).err().unwrap().to_string();
+
+        assert_eq!(
+            error(r#"{ "version": 2, "components": [], "connections": [] }"#),
+            ProjectError::UnsupportedVersion(2).to_string()
+        );
+
+        let components = r#"[
+            { "id": 0, "kind": { "type": "input", "value": "1" } },
+            { "id": 5, "kind": { "type": "not" } }
+        ]"#;
+        let with_connection = |from: (usize, usize), to: (usize, usize)| {
+            format!(
+                r#"{{ "version": 1, "components": {components}, "connections": [
+                    {{ "from": {{ "component": {}, "pin": {} }}, "to": {{ "component": {}, "pin": {} }} }}
+                ] }}"#,
+                from.0, from.1, to.0, to.1
+            )
+        };
+
+        assert!(from_str(&with_connection((0, 0), (5, 0))).is_ok());
+        assert_eq!(
+            error(&with_connection((0, 0), (1, 0))),
+            ProjectError::UnknownComponent(1).to_string()
+        );
+        assert_eq!(
+            error(&with_connection((0, 1), (5, 0))),
+            "component 0 has no output pin 1"
+        );
+        assert_eq!(
+            error(&with_connection((5, 0), (0, 0))),
+            "component 5 has no output pin 0"
+        );
+        assert_eq!(
+            error(&with_connection((0, 0), (5, 1))),
+            "component 5 has no input pin 1"
+        );
+
+        // Gates without an input count have two inputs
+        let (sim, _, _) = from_str(
+            r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "and" } }], "connections": [] }"#,
+        )
+        .unwrap();
+        assert_eq!(sim.components[0].borrow().input_len(), 2);
+
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "or", "inputs": 1 } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidInputCount(1).to_string()
+        );
+
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "rom", "address": 20, "width": 8 } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidMemorySize {
+                address: 20,
+                width: 8
+            }
+            .to_string()
+        );
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "ram", "address": 2, "width": 4, "contents": "1 ff" } }], "connections": [] }"#
+            ),
+            "invalid memory contents: line 1: 'ff' isn't a valid word"
+        );
+
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "splitter", "width": 0 } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidWidth(0).to_string()
+        );
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "multiplexer", "select": 2, "width": 1000000000 } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidWidth(1000000000).to_string()
+        );
+
+        // Switches and constants can't float
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "constant", "value": "Z" } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidValue("Z".to_string()).to_string()
+        );
+        assert_eq!(
+            error(
+                r#"{ "version": 1, "components": [{ "id": 0, "kind": { "type": "toggle_switch", "value": "10" } }], "connections": [] }"#
+            ),
+            ProjectError::InvalidValue("10".to_string()).to_string()
+        );
+    }
+
+    #[test]
+    fn test_memory_contents() {
+        let mut memory = Memory::new(4, 8);
+        memory.set(1, 0xab);
+
+        let mut sim = Simulator::new();
+        sim.insert_component(Component::Rom(memory.clone()));
+        sim.insert_component(Component::Ram(Memory::new(2, 4)));
+
+        let source = to_string(&sim, &[], &Settings::default()).unwrap();
+        let (loaded, _, _) = from_str(&source).unwrap();
+        assert_eq!(
+            loaded.with_memory(ComponentHandle(0), |loaded| loaded == &memory),
+            Some(true)
+        );
+        assert_eq!(
+            loaded.with_memory(ComponentHandle(1), |loaded| loaded.words().to_vec()),
+            Some(vec![0; 4])
+        );
+    }
+}
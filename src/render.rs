@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use egui::{load::SizedTexture, Frame};
 use vello::{
     block_on_wgpu,
@@ -8,20 +10,100 @@ use vello::{
 };
 use winit::window::Window;
 
-use crate::element::ElementManager;
+use crate::{
+    element::{ElementManager, Grid},
+    palette,
+    routing::WireStyle,
+    Component, ComponentHandle,
+};
 
 pub struct UiState {
     viewport_tex: egui::TextureId,
+    /// Number of inputs given to gates placed from the palette
+    gate_inputs: usize,
+    /// File the memory inspector loads contents from
+    memory_path: String,
+    /// Why the last load failed
+    memory_error: Option<String>,
 }
 
 impl UiState {
     pub fn new(viewport: egui::TextureId) -> UiState {
         UiState {
             viewport_tex: viewport,
+            gate_inputs: 2,
+            memory_path: String::new(),
+            memory_error: None,
         }
     }
 }
 
+/// Loads the contents of a ROM or RAM from a file and shows every word, which can be edited while the
+/// simulation runs
+fn memory_inspector(
+    ui: &mut egui::Ui,
+    state: &mut UiState,
+    em: &mut ElementManager,
+    handle: ComponentHandle,
+) {
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.memory_path)
+                .hint_text("contents.hex")
+                .desired_width(100.0),
+        );
+
+        if ui.button("Load").clicked() {
+            let path = Path::new(&state.memory_path);
+            state.memory_error = match em.edit_memory(handle, |memory| memory.load(path)) {
+                Some(Err(err)) => Some(format!("Can't load {}: {err}", path.display())),
+                _ => None,
+            };
+        }
+    });
+
+    if let Some(err) = &state.memory_error {
+        ui.colored_label(ui.visuals().error_fg_color, err);
+    }
+
+    let Some((address_bits, width, mask)) = em.sim().with_memory(handle, |memory| {
+        (memory.address, memory.width, memory.mask())
+    }) else {
+        return;
+    };
+    let address_digits = address_bits.div_ceil(4);
+
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    egui::ScrollArea::vertical().max_height(200.0).show_rows(
+        ui,
+        row_height,
+        1 << address_bits,
+        |ui, rows| {
+            for address in rows {
+                let Some(mut word) = em
+                    .sim()
+                    .with_memory(handle, |memory| memory.words()[address])
+                else {
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{address:0address_digits$x}"));
+
+                    let response = ui.add(
+                        egui::DragValue::new(&mut word)
+                            .hexadecimal(width.div_ceil(4), false, false)
+                            .clamp_range(0..=mask),
+                    );
+                    if response.changed() {
+                        em.edit_memory(handle, |memory| memory.set(address, word));
+                    }
+                });
+            }
+        },
+    );
+}
+
 pub struct RenderManager {
     pub ctx: RenderContext,
     pub main_surface: RenderSurface,
@@ -135,31 +217,116 @@ impl RenderManager {
     }
 
     fn gui(&mut self, ctx: &egui::Context) {
-        egui::SidePanel::right("egui_demo_panel")
-            // .resizable(false)
+        egui::SidePanel::right("palette_panel")
             .default_width(150.0)
             .show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
-                    ui.heading("✒ egui demos");
+                    ui.heading("Components");
                 });
 
                 ui.separator();
 
-                use egui::special_emojis::{GITHUB, TWITTER};
-                ui.hyperlink_to(
-                    format!("{GITHUB} egui on GitHub"),
-                    "https://github.com/emilk/egui",
+                ui.add(
+                    egui::Slider::new(&mut self.ui_state.gate_inputs, Component::GATE_INPUTS)
+                        .text("Gate inputs"),
                 );
-                ui.hyperlink_to(
-                    format!("{TWITTER} @ernerfeldt"),
-                    "https://twitter.com/ernerfeldt",
+
+                // Click an entry and then the canvas to place it, or drag it onto the canvas
+                for entry in palette::ENTRIES {
+                    let response = ui.add_sized(
+                        [ui.available_width(), 0.0],
+                        egui::Button::new(entry.name).sense(egui::Sense::click_and_drag()),
+                    );
+
+                    if response.clicked() || response.drag_started() {
+                        self.element_manager
+                            .start_placing(entry.build(self.ui_state.gate_inputs));
+                    }
+                }
+
+                ui.separator();
+
+                let em = &mut self.element_manager;
+                ui.heading("Simulation");
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(if em.running { "Pause" } else { "Run" })
+                        .clicked()
+                    {
+                        em.running = !em.running;
+                    }
+
+                    // Stepping only makes sense while paused
+                    if ui
+                        .add_enabled(!em.running, egui::Button::new("Step"))
+                        .clicked()
+                    {
+                        em.step();
+                    }
+                });
+                ui.add(
+                    egui::Slider::new(&mut em.speed, 1.0..=1000.0)
+                        .logarithmic(true)
+                        .text("Steps/s"),
                 );
+                ui.label(format!("t = {}", em.sim().time()));
+
+                ui.separator();
+
+                if let Some(handle) = em.selected_memory() {
+                    ui.heading("Memory");
+                    memory_inspector(ui, &mut self.ui_state, em, handle);
+
+                    ui.separator();
+                }
+
+                ui.heading("Wires");
+                let wire_style = &mut self.element_manager.settings.wire_style;
+                ui.radio_value(wire_style, WireStyle::Bezier, "Curved");
+                ui.radio_value(wire_style, WireStyle::Orthogonal, "Orthogonal");
 
                 ui.separator();
 
-                // self.demo_list_ui(ui);
+                let grid = &mut self.element_manager.grid;
+                ui.heading("Grid");
+                ui.checkbox(&mut grid.visible, "Show grid");
+                ui.checkbox(&mut grid.snap, "Snap to grid (hold alt to disable)");
+                let mut spacing = grid.spacing;
+                if ui
+                    .add(
+                        egui::Slider::new(&mut spacing, Grid::SPACINGS)
+                            .step_by(5.0)
+                            .text("Spacing"),
+                    )
+                    .changed()
+                {
+                    self.element_manager.set_grid_spacing(spacing);
+                }
             });
 
+        // Clipboard shortcuts arrive through egui, which also talks to the system clipboard. They belong to a text
+        // field instead while one has focus.
+        let events = if ctx.wants_keyboard_input() {
+            Vec::new()
+        } else {
+            ctx.input(|input| input.events.clone())
+        };
+        for event in events {
+            let copied = match event {
+                egui::Event::Copy => self.element_manager.copy_selection(),
+                egui::Event::Cut => self.element_manager.cut_selection(),
+                egui::Event::Paste(text) => {
+                    self.element_manager.paste(&text);
+                    None
+                }
+                _ => None,
+            };
+
+            if let Some(text) = copied {
+                ctx.output_mut(|output| output.copied_text = text);
+            }
+        }
+
         egui::CentralPanel::default()
             .frame(Frame::none())
             .show(ctx, |ui| {
@@ -170,6 +337,18 @@ impl RenderManager {
                 //                 )
 
                 let size = ui.available_size();
+                let view = ui.available_rect_before_wrap();
+                // egui works in logical points while winit reports the mouse in physical pixels
+                self.element_manager.set_view(
+                    Rect::new(
+                        view.min.x as f64,
+                        view.min.y as f64,
+                        view.max.x as f64,
+                        view.max.y as f64,
+                    )
+                    .scale_from_origin(ui.ctx().pixels_per_point() as f64),
+                );
+
                 self.resize_viewport(size.x as u32, size.y as u32);
                 self.draw();
                 egui::Image::new(SizedTexture::new(self.ui_state.viewport_tex, size))
@@ -195,6 +374,7 @@ impl RenderManager {
                 .update_texture(&device.device, &device.queue, delta.0, &delta.1);
         }
 
+        platform.handle_platform_output(window, context, output.platform_output);
         self.gui_output = context.tessellate(output.shapes);
     }
 
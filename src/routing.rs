@@ -0,0 +1,284 @@
+//! Orthogonal wire routing. Wires are searched with A* over the grid, going around the bodies of elements and
+//! preferring paths with few bends.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use serde::{Deserialize, Serialize};
+use vello::kurbo::{Point, Rect, Vec2};
+
+/// How connections are drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireStyle {
+    /// Cubic curves straight from pin to pin
+    #[default]
+    Bezier,
+    /// Axis aligned lines routed around elements
+    Orthogonal,
+}
+
+/// Cost of a bend, in grid steps
+const BEND_COST: u32 = 4;
+/// How far the search may wander outside the box spanned by both ends, in grid steps
+const MARGIN: i64 = 10;
+/// Nodes visited before giving up and falling back to a plain three segment wire
+const SEARCH_LIMIT: usize = 20_000;
+
+type Node = (i64, i64);
+
+/// Right, down, left, up
+const DIRECTIONS: [Node; 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// Routes found in earlier frames, so the search only runs again for wires whose ends moved. Every route is
+/// dropped once an obstacle moves or the grid spacing changes, since that can change any of them.
+#[derive(Default)]
+pub struct RouteCache {
+    obstacles: Vec<Rect>,
+    spacing: f64,
+    routes: HashMap<[u64; 4], Vec<Point>>,
+}
+
+impl RouteCache {
+    /// Sets the obstacles and grid spacing the routes go around, dropping the routes if either changed
+    pub fn update(&mut self, obstacles: &[Rect], spacing: f64) {
+        if self.obstacles != obstacles || self.spacing != spacing {
+            self.obstacles = obstacles.to_vec();
+            self.spacing = spacing;
+            self.routes.clear();
+        }
+    }
+
+    /// Same as `route` with the obstacles and spacing from the last `update`
+    pub fn route(&mut self, from: Point, to: Point) -> &[Point] {
+        let key = [from.x, from.y, to.x, to.y].map(f64::to_bits);
+
+        self.routes
+            .entry(key)
+            .or_insert_with(|| route(from, to, &self.obstacles, self.spacing))
+    }
+}
+
+/// Axis aligned polyline from an output pin at `from` to an input pin at `to` that stays out of `obstacles`.
+/// Wires leave outputs to the right and enter inputs from the left.
+pub fn route(from: Point, to: Point, obstacles: &[Rect], spacing: f64) -> Vec<Point> {
+    let node = |point: Point| {
+        (
+            (point.x / spacing).round() as i64,
+            (point.y / spacing).round() as i64,
+        )
+    };
+    let point = |(x, y): Node| Point::new(x as f64 * spacing, y as f64 * spacing);
+
+    let start = node(from + Vec2::new(spacing, 0.0));
+    let goal = node(to - Vec2::new(spacing, 0.0));
+
+    let obstacles = obstacles
+        .iter()
+        .map(|obstacle| obstacle.inflate(spacing / 2.0, spacing / 2.0))
+        .collect::<Vec<_>>();
+    let blocked = |node: Node| {
+        node != start
+            && node != goal
+            && obstacles
+                .iter()
+                .any(|obstacle| obstacle.contains(point(node)))
+    };
+
+    let min = (start.0.min(goal.0) - MARGIN, start.1.min(goal.1) - MARGIN);
+    let max = (start.0.max(goal.0) + MARGIN, start.1.max(goal.1) + MARGIN);
+    let heuristic = |(x, y): Node| (x.abs_diff(goal.0) + y.abs_diff(goal.1)) as u32;
+
+    // Search states are a node and the direction the wire took to get there, so bends can be charged
+    let mut costs: HashMap<(Node, usize), u32> = HashMap::from([((start, 0), 0)]);
+    let mut came_from: HashMap<(Node, usize), (Node, usize)> = HashMap::new();
+    let mut open = BinaryHeap::from([Reverse((heuristic(start), start, 0))]);
+
+    let mut found = None;
+    for _ in 0..SEARCH_LIMIT {
+        let Some(Reverse((_, current, direction))) = open.pop() else {
+            break;
+        };
+
+        if current == goal {
+            found = Some((current, direction));
+            break;
+        }
+
+        let cost = costs[&(current, direction)];
+        for (next_direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+            // Turning back on itself is never useful
+            if next_direction == (direction + 2) % 4 {
+                continue;
+            }
+
+            let next = (current.0 + dx, current.1 + dy);
+            if next.0 < min.0 || next.1 < min.1 || next.0 > max.0 || next.1 > max.1 || blocked(next)
+            {
+                continue;
+            }
+
+            let next_cost = cost
+                + 1
+                + if next_direction == direction {
+                    0
+                } else {
+                    BEND_COST
+                };
+            let state = (next, next_direction);
+            if costs.get(&state).is_some_and(|known| *known <= next_cost) {
+                continue;
+            }
+
+            costs.insert(state, next_cost);
+            came_from.insert(state, (current, direction));
+            open.push(Reverse((next_cost + heuristic(next), next, next_direction)));
+        }
+    }
+
+    let Some(mut state) = found else {
+        let middle = (from.x + to.x) / 2.0;
+        return simplify(vec![
+            from,
+            Point::new(middle, from.y),
+            Point::new(middle, to.y),
+            to,
+        ]);
+    };
+
+    let mut nodes = vec![state.0];
+    while let Some(previous) = came_from.get(&state) {
+        nodes.push(previous.0);
+        state = *previous;
+    }
+    nodes.reverse();
+
+    // The pins don't have to be on the grid, so jog over to the first and last node
+    let first = point(start);
+    let last = point(goal);
+
+    let mut points = vec![from, Point::new(first.x, from.y)];
+    points.extend(nodes.into_iter().map(point));
+    points.extend([Point::new(last.x, to.y), to]);
+
+    simplify(points)
+}
+
+/// Drops repeated points and points in the middle of straight runs
+fn simplify(points: Vec<Point>) -> Vec<Point> {
+    let mut simplified: Vec<Point> = Vec::with_capacity(points.len());
+
+    for point in points {
+        if simplified.last() == Some(&point) {
+            continue;
+        }
+
+        if let [.., a, b] = simplified[..] {
+            if (a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y) {
+                simplified.pop();
+            }
+        }
+
+        simplified.push(point);
+    }
+
+    simplified
+}
+
+/// Point halfway along a polyline
+pub fn midpoint(points: &[Point]) -> Point {
+    let length: f64 = points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+
+    let mut remaining = length / 2.0;
+    for pair in points.windows(2) {
+        let segment = pair[0].distance(pair[1]);
+        if remaining <= segment && segment > 0.0 {
+            return pair[0].lerp(pair[1], remaining / segment);
+        }
+
+        remaining -= segment;
+    }
+
+    points.first().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn is_orthogonal(points: &[Point]) -> bool {
+        points
+            .windows(2)
+            .all(|pair| pair[0].x == pair[1].x || pair[0].y == pair[1].y)
+    }
+
+    #[test]
+    fn test_route_straight() {
+        let points = route(Point::new(0.0, 100.0), Point::new(200.0, 100.0), &[], 20.0);
+
+        assert_eq!(
+            points,
+            vec![Point::new(0.0, 100.0), Point::new(200.0, 100.0)]
+        );
+    }
+
+    #[test]
+    fn test_route_around_obstacle() {
+        let obstacle = Rect::new(80.0, 40.0, 120.0, 160.0);
+        let points = route(
+            Point::new(0.0, 100.0),
+            Point::new(200.0, 100.0),
+            &[obstacle],
+            20.0,
+        );
+
+        assert!(is_orthogonal(&points));
+        assert_eq!(points.first(), Some(&Point::new(0.0, 100.0)));
+        assert_eq!(points.last(), Some(&Point::new(200.0, 100.0)));
+
+        // No segment passes through the obstacle
+        for pair in points.windows(2) {
+            for i in 0..=20 {
+                assert!(!obstacle.contains(pair[0].lerp(pair[1], i as f64 / 20.0)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_route_off_grid() {
+        let points = route(Point::new(3.0, 33.0), Point::new(157.0, 141.0), &[], 20.0);
+
+        assert!(is_orthogonal(&points));
+        assert_eq!(points.first(), Some(&Point::new(3.0, 33.0)));
+        assert_eq!(points.last(), Some(&Point::new(157.0, 141.0)));
+    }
+
+    #[test]
+    fn test_route_cache() {
+        let mut cache = RouteCache::default();
+        let (from, to) = (Point::new(0.0, 100.0), Point::new(200.0, 100.0));
+
+        cache.update(&[], 20.0);
+        assert_eq!(cache.route(from, to), &[from, to]);
+
+        // Moving an obstacle into the way routes the wire again
+        cache.update(&[Rect::new(80.0, 40.0, 120.0, 160.0)], 20.0);
+        assert!(cache.route(from, to).len() > 2);
+    }
+
+    #[test]
+    fn test_midpoint() {
+        let points = [
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+        ];
+
+        assert_eq!(midpoint(&points), Point::new(10.0, 0.0));
+    }
+}